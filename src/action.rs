@@ -0,0 +1,292 @@
+use smithay::{
+    desktop,
+    input::pointer::GrabStartData,
+    reexports::{calloop, wayland_server::protocol::wl_surface::WlSurface},
+    utils::SERIAL_COUNTER,
+};
+use tracing::{error, warn};
+
+/// Left mouse button, see `linux/input-event-codes.h`
+const BTN_LEFT: u32 = 0x110;
+
+/// Window an [Action] applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// The window with keyboard focus
+    Focused,
+    /// The window of the toplevel surface
+    Window(WlSurface),
+}
+
+/// Something the compositor can be told to do by keybindings or the shell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Run a shell command
+    Spawn(String),
+    /// Focuses the window, showing its workspace and unminimizing it first
+    Focus(WlSurface),
+    FocusNext,
+    FocusPrevious,
+    Close(Target),
+    Raise(Target),
+    /// Drag the window with the pointer until the button is released
+    Move(Target),
+    /// Switches the window between floating and tiled
+    ToggleFloating(Target),
+    ToggleFullscreen(Target),
+    ToggleMaximize(Target),
+    Minimize(Target),
+    /// Shows the workspace with the number on the shell output, creating it if needed
+    SwitchWorkspace(usize),
+    MoveToWorkspace(Target, usize),
+    SetLayout(crate::layout::LayoutKind),
+    NextLayout,
+    /// Grows the master area by the percentage points, shrinking it when negative
+    AdjustMasterRatio(i32),
+    AdjustGaps(i32),
+    Quit,
+    ReloadConfig,
+}
+
+/// Creates the sender for queueing actions where the whole [crate::App] is not accessible.
+/// The actions are run on the next event loop dispatch
+pub fn init_bus<B: crate::Backend>(
+    loop_handle: &calloop::LoopHandle<'static, crate::App<B>>,
+) -> calloop::channel::Sender<Action> {
+    let (sender, receiver) = calloop::channel::channel::<Action>();
+
+    loop_handle
+        .insert_source(receiver, |event, _, app| {
+            if let calloop::channel::Event::Msg(action) = event {
+                app.run_action(action);
+            }
+        })
+        .expect("Unable to insert action bus source");
+
+    sender
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    pub fn run_action(&mut self, action: Action) {
+        // Most actions change what the shell shows
+        self.common.comp.schedule_redraw();
+
+        match action {
+            Action::Spawn(command) => {
+                // The command is started in the background by an intermediate shell that exits
+                // right away. The command is then reparented to init, which reaps it, so it
+                // does not linger as a zombie once it exits
+                if let Err(err) = std::process::Command::new("sh")
+                    .args(["-c", "sh -c \"$1\" &", "sh", &command])
+                    .status()
+                {
+                    error!("Unable to spawn {command:?}: {err}");
+                }
+            }
+            Action::Focus(surface) => {
+                if let Some(window) = self.target_window(&Target::Window(surface)) {
+                    self.show_window(&window);
+                    self.focus_window(&window);
+                }
+            }
+            Action::FocusNext => self.cycle_focus(1),
+            Action::FocusPrevious => self.cycle_focus(-1),
+            Action::Close(target) => {
+                if let Some(window) = self.target_window(&target) {
+                    crate::util::surface::close_window(&window);
+                }
+            }
+            Action::Raise(target) => {
+                if let Some(window) = self.target_window(&target) {
+                    self.show_window(&window);
+                    self.common.comp.space.raise_element(&window, true);
+                    self.focus_window(&window);
+                }
+            }
+            Action::Move(target) => {
+                if let Some(window) = self.target_window(&target) {
+                    self.focus_window(&window);
+                    self.start_move_with_button(window);
+                }
+            }
+            Action::ToggleFloating(target) => {
+                if let Some(window) = self.target_window(&target) {
+                    let comp = &mut self.common.comp;
+                    if comp.floating(&window).is_none() {
+                        comp.float(&window);
+                    } else if let Some(workspace) = comp.workspaces.containing_mut(&window) {
+                        workspace.tile(&window);
+                    }
+                }
+            }
+            Action::ToggleFullscreen(target) => {
+                if let Some(window) = self.target_window(&target) {
+                    if let Some(workspace) = self.common.comp.workspaces.containing_mut(&window) {
+                        let fullscreen = workspace.fullscreen.as_ref() != Some(&window);
+                        workspace.set_fullscreen(&window, fullscreen);
+                    }
+                }
+            }
+            Action::ToggleMaximize(target) => {
+                if let Some(window) = self.target_window(&target) {
+                    if let Some(workspace) = self.common.comp.workspaces.containing_mut(&window) {
+                        let maximized = workspace.maximized.as_ref() != Some(&window);
+                        workspace.set_maximized(&window, maximized);
+                    }
+                }
+            }
+            Action::Minimize(target) => {
+                if let Some(window) = self.target_window(&target) {
+                    if let Some(workspace) = self.common.comp.workspaces.containing_mut(&window) {
+                        workspace.set_maximized(&window, false);
+                        workspace.set_fullscreen(&window, false);
+                        if !workspace.minimized.contains(&window) {
+                            workspace.minimized.push(window.clone());
+                        }
+                    }
+
+                    self.unfocus(&window);
+                }
+            }
+            Action::SwitchWorkspace(number) => self.switch_workspace(number),
+            Action::MoveToWorkspace(target, number) => {
+                if number == 0 {
+                    warn!("Unable to move a window to workspace 0, workspaces start at 1");
+                } else if let Some(window) = self.target_window(&target) {
+                    let workspaces = &mut self.common.comp.workspaces;
+                    if workspaces.number_of(&window) != Some(number) {
+                        if let Some(workspace) = workspaces.containing_mut(&window) {
+                            workspace.remove(&window);
+                        }
+                        workspaces
+                            .get_or_create(number)
+                            .windows
+                            .push(window.clone());
+                        self.unfocus(&window);
+                    }
+                }
+            }
+            Action::SetLayout(kind) => self.common.comp.workspace_mut().layout.kind = kind,
+            Action::NextLayout => {
+                let layout = &mut self.common.comp.workspace_mut().layout;
+                layout.kind = layout.kind.next();
+            }
+            Action::AdjustMasterRatio(percent) => self
+                .common
+                .comp
+                .workspace_mut()
+                .layout
+                .adjust_master_ratio(percent as f32 / 100.),
+            Action::AdjustGaps(delta) => self.common.comp.workspace_mut().layout.adjust_gaps(delta),
+            Action::Quit => self.common.comp.loop_signal.stop(),
+            Action::ReloadConfig => self.reload_config(),
+        }
+    }
+
+    /// Shows the workspace and focuses its first window
+    fn switch_workspace(&mut self, number: usize) {
+        if number == 0 {
+            warn!("Unable to switch to workspace 0, workspaces start at 1");
+            return;
+        }
+
+        let comp = &mut self.common.comp;
+        let Some(output) = comp.shell_output().cloned() else {
+            return;
+        };
+        comp.workspaces.show(&output, number);
+
+        let workspace = comp.workspace();
+        let window = workspace
+            .windows
+            .iter()
+            .find(|window| !workspace.minimized.contains(window))
+            .cloned();
+        match window {
+            Some(window) => self.focus_window(&window),
+            None => self.clear_focus(),
+        }
+    }
+
+    /// Switches to the workspace of the window and brings it back if it was minimized,
+    /// so it is not hidden when it gets the focus
+    fn show_window(&mut self, window: &desktop::Window) {
+        let comp = &self.common.comp;
+        if let Some(number) = comp.workspaces.number_of(window) {
+            if number != comp.shown_workspace() {
+                self.switch_workspace(number);
+            }
+        }
+
+        if let Some(workspace) = self.common.comp.workspaces.containing_mut(window) {
+            workspace.minimized.retain(|minimized| minimized != window);
+        }
+    }
+
+    /// Moves the focus away from a window that is no longer shown
+    pub fn unfocus(&mut self, window: &desktop::Window) {
+        if self.focused_window().as_ref() == Some(window) {
+            self.cycle_focus(1);
+        }
+        // Hidden windows must not keep the focus when there is nothing else
+        if self.focused_window().as_ref() == Some(window) {
+            self.clear_focus();
+        }
+    }
+
+    fn clear_focus(&mut self) {
+        if let Some(keyboard) = self.common.comp.seat.get_keyboard() {
+            keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    /// Starts dragging the window with the left button pressed on the shell.
+    /// Actions run asynchronously, so the button may have been released in the meantime
+    fn start_move_with_button(&mut self, window: desktop::Window) {
+        let Some(pointer) = self.common.comp.seat.get_pointer() else {
+            return;
+        };
+        // The grab only ends on a release, which would never come
+        if !self.common.comp.shell_buttons.contains(&BTN_LEFT) {
+            return;
+        }
+
+        let start_data = GrabStartData {
+            focus: None,
+            button: BTN_LEFT,
+            location: pointer.current_location(),
+        };
+        self.start_move(window, start_data, SERIAL_COUNTER.next_serial());
+    }
+
+    fn target_window(&self, target: &Target) -> Option<desktop::Window> {
+        match target {
+            Target::Focused => self.focused_window(),
+            Target::Window(surface) => {
+                crate::util::surface::find_window(surface, &self.common.comp.windows)
+            }
+        }
+    }
+
+    /// Moves the focus by the offset through the windows of the shown workspace in layout order
+    fn cycle_focus(&mut self, offset: isize) {
+        let workspace = self.common.comp.workspace();
+        let windows: Vec<_> = workspace
+            .windows
+            .iter()
+            .filter(|window| !workspace.minimized.contains(window))
+            .cloned()
+            .collect();
+        if windows.is_empty() {
+            return;
+        }
+
+        let current = self
+            .focused_window()
+            .and_then(|focused| windows.iter().position(|window| window == &focused))
+            .unwrap_or(0);
+        let next = (current as isize + offset).rem_euclid(windows.len() as isize) as usize;
+
+        self.focus_window(&windows[next]);
+    }
+}
@@ -0,0 +1,302 @@
+use smithay::{
+    backend::{
+        allocator::{self, Fourcc},
+        egl::{EGLContext, EGLDevice, EGLDisplay},
+        renderer::{
+            damage::OutputDamageTracker,
+            gles::{GlesRenderer, GlesTexture},
+            Bind, ExportMem, ImportDma, Offscreen,
+        },
+    },
+    output,
+    reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+    utils::{Buffer, Physical, Rectangle, Size, Transform},
+    wayland::dmabuf::DmabufFeedbackBuilder,
+};
+use tracing::error;
+
+const OUTPUT_SIZE: (i32, i32) = (1920, 1080);
+const FRAME_FORMAT: Fourcc = Fourcc::Abgr8888;
+
+pub type HeadlessApp = crate::App<HeadlessBackend>;
+
+/// Backend rendering into offscreen buffers. Requires a render node but no window or display server
+pub struct HeadlessBackend {
+    pub renderer: GlesRenderer,
+    pub outputs: Vec<HeadlessOutput>,
+}
+
+pub struct HeadlessOutput {
+    pub output: output::Output,
+    pub damage_tracker: OutputDamageTracker,
+    pub buffer: GlesTexture,
+    pub last_frame: Option<Frame>,
+}
+
+/// Pixel data of a rendered frame
+pub struct Frame {
+    pub size: Size<i32, Physical>,
+    pub format: Fourcc,
+    pub data: Vec<u8>,
+}
+
+impl super::Backend for HeadlessBackend {
+    type SelfType = HeadlessBackend;
+
+    fn new(common: &mut crate::state::Common<Self::SelfType>) -> Self {
+        let device = EGLDevice::enumerate()
+            .expect("Unable to enumerate EGL devices")
+            .find(|device| matches!(device.try_get_render_node(), Ok(Some(_))))
+            .expect("No EGL device with a render node found");
+
+        let display = unsafe { EGLDisplay::new(device) }.expect("Unable to create EGL display");
+        let context = EGLContext::new(&display).expect("Unable to create EGL context");
+        let renderer =
+            unsafe { GlesRenderer::new(context) }.expect("Unable to create gles renderer");
+
+        common
+            .comp
+            .seat
+            .add_keyboard(
+                common.comp.config.input.keyboard.xkb_config(),
+                common.comp.config.input.keyboard.repeat_delay,
+                common.comp.config.input.keyboard.repeat_rate,
+            )
+            .expect("Unable to initialize keyboard");
+        common.comp.seat.add_pointer();
+
+        let mut backend = Self {
+            renderer,
+            outputs: Vec::new(),
+        };
+        backend.add_output(common, OUTPUT_SIZE.into());
+
+        backend
+    }
+
+    fn default_dmabuf_feedback(&mut self) -> Option<smithay::wayland::dmabuf::DmabufFeedback> {
+        let display = self.renderer.egl_context().display();
+        let device = EGLDevice::device_for_display(display).ok()?;
+        let render_node = device.try_get_render_node().ok()??;
+        DmabufFeedbackBuilder::new(render_node.dev_id(), self.renderer.dmabuf_formats())
+            .build()
+            .ok()
+    }
+
+    fn dmabuf_formats(&mut self) -> allocator::format::FormatSet {
+        self.renderer.dmabuf_formats()
+    }
+
+    fn import_dmabuf(&mut self, dmabuf: &allocator::dmabuf::Dmabuf) -> std::result::Result<(), ()> {
+        match self.renderer.import_dmabuf(dmabuf, None) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed importing dmabuf: {e:?}");
+                Err(())
+            }
+        }
+    }
+
+    fn render(app: &mut HeadlessApp) {
+        app.render();
+    }
+}
+
+impl HeadlessBackend {
+    /// Creates a virtual output placed right of the existing ones
+    pub fn add_output(
+        &mut self,
+        common: &mut crate::state::Common<Self>,
+        size: Size<i32, Physical>,
+    ) -> output::Output {
+        let output = output::Output::new(
+            format!("headless-{}", self.outputs.len()),
+            output::PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: output::Subpixel::Unknown,
+                make: "Chadland".to_string(),
+                model: "Headless".to_string(),
+            },
+        );
+
+        let x = self
+            .outputs
+            .iter()
+            .filter_map(|o| common.comp.space.output_geometry(&o.output))
+            .map(|geometry| geometry.loc.x + geometry.size.w)
+            .max()
+            .unwrap_or(0);
+
+        output.change_current_state(
+            Some(output::Mode {
+                size,
+                refresh: common.comp.config.refresh_rate(&output.name()) * 1000,
+            }),
+            Some(Transform::Normal),
+            Some(common.comp.config.scale(&output.name(), 1.)),
+            Some((x, 0).into()),
+        );
+        output.set_preferred(output.current_mode().expect("Output has no current mode"));
+
+        output.create_global::<HeadlessApp>(&common.comp.display_handle);
+
+        common.comp.space.map_output(&output, (x, 0));
+
+        let buffer = Offscreen::<GlesTexture>::create_buffer(
+            &mut self.renderer,
+            FRAME_FORMAT,
+            size.to_logical(1).to_buffer(1, Transform::Normal),
+        )
+        .expect("Unable to create offscreen buffer");
+
+        self.outputs.push(HeadlessOutput {
+            output: output.clone(),
+            damage_tracker: OutputDamageTracker::from_output(&output),
+            buffer,
+            last_frame: None,
+        });
+        common.comp.schedule_redraw();
+
+        output
+    }
+
+    /// Returns the most recently rendered frame of the output
+    pub fn last_frame(&self, output: &output::Output) -> Option<&Frame> {
+        self.outputs
+            .iter()
+            .find(|o| &o.output == output)
+            .and_then(|o| o.last_frame.as_ref())
+    }
+}
+
+impl HeadlessApp {
+    pub fn render(&mut self) {
+        for index in 0..self.backend.outputs.len() {
+            let output = self.backend.outputs[index].output.clone();
+
+            let elements = crate::render::output_elements(
+                &mut self.backend.renderer,
+                &output,
+                &mut self.common,
+            );
+
+            let headless_output = &mut self.backend.outputs[index];
+
+            self.backend
+                .renderer
+                .bind(headless_output.buffer.clone())
+                .expect("Unable to bind offscreen buffer");
+
+            // The offscreen buffer keeps the previous frame once there is one
+            let age = headless_output.last_frame.is_some() as usize;
+            let result = headless_output
+                .damage_tracker
+                .render_output(
+                    &mut self.backend.renderer,
+                    age,
+                    &elements,
+                    self.common.comp.config.theme.background,
+                )
+                .expect("Unable to render output");
+            let mut feedback = crate::render::post_repaint(&self.common, &output, &result.states);
+
+            // Nothing changed, so the last frame still shows what the buffer holds
+            // and the feedback is discarded when dropped
+            if result.damage.is_some() {
+                let size = output
+                    .current_mode()
+                    .expect("Output has no current mode")
+                    .size;
+
+                match read_frame(&mut self.backend.renderer, size) {
+                    Ok(frame) => headless_output.last_frame = Some(frame),
+                    Err(e) => error!("Unable to read back rendered frame: {e:?}"),
+                }
+
+                // The frame counts as presented once it can be read back
+                feedback.presented(
+                    self.common.comp.clock.now(),
+                    crate::render::refresh(&output),
+                    0,
+                    wp_presentation_feedback::Kind::empty(),
+                );
+            }
+
+            crate::render::send_preferred_scales(&self.common.comp, &output);
+        }
+    }
+}
+
+fn read_frame(
+    renderer: &mut GlesRenderer,
+    size: Size<i32, Physical>,
+) -> Result<Frame, smithay::backend::renderer::gles::GlesError> {
+    let region = Rectangle::<_, Buffer>::from_loc_and_size(
+        (0, 0),
+        size.to_logical(1).to_buffer(1, Transform::Normal),
+    );
+    let mapping = renderer.copy_framebuffer(region, FRAME_FORMAT)?;
+    let data = renderer.map_texture(&mapping)?.to_vec();
+
+    Ok(Frame {
+        size,
+        format: FRAME_FORMAT,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smithay::reexports::{calloop, wayland_server};
+
+    fn app(
+        event_loop: &calloop::EventLoop<'static, HeadlessApp>,
+        display: &wayland_server::Display<HeadlessApp>,
+    ) -> HeadlessApp {
+        let mut common = crate::state::Common::new(
+            display.handle(),
+            event_loop.handle(),
+            event_loop.get_signal(),
+            crate::config::Config::default(),
+        );
+        let backend = <HeadlessBackend as crate::Backend>::new(&mut common);
+        crate::App { common, backend }
+    }
+
+    #[test]
+    fn renders_added_output() {
+        let has_render_node = EGLDevice::enumerate().is_ok_and(|mut devices| {
+            devices.any(|device| matches!(device.try_get_render_node(), Ok(Some(_))))
+        });
+        if !has_render_node {
+            eprintln!("Skipping, no EGL device with a render node found");
+            return;
+        }
+
+        let event_loop = calloop::EventLoop::try_new().unwrap();
+        let display = wayland_server::Display::new().unwrap();
+        let mut app = app(&event_loop, &display);
+
+        let output = app.backend.add_output(&mut app.common, (640, 480).into());
+        app.render();
+
+        let frame = app
+            .backend
+            .last_frame(&output)
+            .expect("Output was not rendered");
+        assert_eq!(frame.size, (640, 480).into());
+        assert_eq!(frame.format, FRAME_FORMAT);
+        assert_eq!(frame.data.len(), 640 * 480 * 4);
+
+        // Only the shell output shows the shell, so the others are cleared with the background
+        let background = app.common.comp.config.theme.background;
+        for (channel, value) in frame.data[..4].iter().zip(background) {
+            assert!(channel.abs_diff((value * 255.).round() as u8) <= 1);
+        }
+
+        // Without damage the previous frame is kept instead of read back again
+        app.render();
+        assert!(app.backend.last_frame(&output).is_some());
+    }
+}
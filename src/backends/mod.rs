@@ -1,5 +1,6 @@
 use smithay::{backend::allocator, wayland::dmabuf::DmabufFeedback};
 
+pub mod headless;
 pub mod winit;
 
 /// Trait for handling input and output
@@ -13,4 +14,14 @@ pub trait Backend: 'static {
     fn default_dmabuf_feedback(&mut self) -> Option<DmabufFeedback>;
     fn dmabuf_formats(&mut self) -> allocator::format::FormatSet;
     fn import_dmabuf(&mut self, dmabuf: &allocator::dmabuf::Dmabuf) -> Result<(), ()>;
+
+    /// Renders all outputs. Called by the [crate::redraw::Scheduler]
+    fn render(app: &mut crate::App<Self>)
+    where
+        Self: Sized;
+
+    /// Scale of the output when none is configured
+    fn native_scale(&self, _output: &smithay::output::Output) -> f64 {
+        1.
+    }
 }
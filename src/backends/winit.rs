@@ -2,18 +2,16 @@ use smithay::{
     backend::{
         allocator,
         egl::EGLDevice,
-        renderer::{damage::OutputDamageTracker, gles::GlesRenderer, Frame, ImportDma, Renderer},
+        renderer::{damage::OutputDamageTracker, gles::GlesRenderer, ImportDma},
         winit::{WinitEvent, WinitGraphicsBackend},
     },
     output,
-    reexports::calloop,
-    utils::{Physical, Rectangle, Transform},
+    reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
     wayland::dmabuf::DmabufFeedbackBuilder,
 };
-use std::time::Duration;
 use tracing::error;
 
-const REFRESH_RATE: i32 = 60;
+const OUTPUT_NAME: &str = "winit";
 
 pub type WinitApp = crate::App<WinitBackend>;
 
@@ -21,6 +19,8 @@ pub struct WinitBackend {
     pub winit: WinitGraphicsBackend<GlesRenderer>,
     pub output: output::Output,
     pub damage_tracker: OutputDamageTracker,
+    /// Scale factor of the window reported by winit
+    pub scale_factor: f64,
 }
 
 impl super::Backend for WinitBackend {
@@ -38,27 +38,20 @@ impl super::Backend for WinitBackend {
             })
             .expect("Unable to insert winit event source");
 
-        let redraw_delay = Duration::from_millis(1000 / REFRESH_RATE as u64);
-        common
-            .comp
-            .loop_handle
-            .insert_source(
-                calloop::timer::Timer::from_duration(redraw_delay),
-                move |_, _, app| {
-                    app.event_handler(WinitEvent::Redraw);
-                    calloop::timer::TimeoutAction::ToDuration(redraw_delay)
-                },
-            )
-            .expect("Unable to insert redraw timer event source");
-
         common
             .comp
             .seat
-            .add_keyboard(smithay::input::keyboard::XkbConfig::default(), 500, 100)
+            .add_keyboard(
+                common.comp.config.input.keyboard.xkb_config(),
+                common.comp.config.input.keyboard.repeat_delay,
+                common.comp.config.input.keyboard.repeat_rate,
+            )
             .expect("Unable to initialize keyboard");
+        common.comp.seat.add_pointer();
 
+        let scale_factor = winit.window().scale_factor();
         let output = output::Output::new(
-            "winit".to_string(),
+            OUTPUT_NAME.to_string(),
             output::PhysicalProperties {
                 size: (0, 0).into(),
                 subpixel: output::Subpixel::Unknown,
@@ -70,11 +63,11 @@ impl super::Backend for WinitBackend {
         output.change_current_state(
             Some(output::Mode {
                 size: winit.window_size(),
-                refresh: REFRESH_RATE * 1000,
+                refresh: common.comp.config.refresh_rate(OUTPUT_NAME) * 1000,
             }),
             // Everything is upside down without transform
             Some(smithay::utils::Transform::Flipped180),
-            Some(output::Scale::Integer(1)),
+            Some(common.comp.config.scale(OUTPUT_NAME, scale_factor)),
             Some((0, 0).into()),
         );
         output.set_preferred(output.current_mode().expect("Output has no current mode"));
@@ -89,6 +82,7 @@ impl super::Backend for WinitBackend {
             winit,
             output,
             damage_tracker,
+            scale_factor,
         }
     }
 
@@ -114,23 +108,35 @@ impl super::Backend for WinitBackend {
             }
         }
     }
+
+    fn native_scale(&self, _output: &output::Output) -> f64 {
+        self.scale_factor
+    }
+
+    fn render(app: &mut WinitApp) {
+        app.render();
+    }
 }
 
 impl WinitApp {
     fn event_handler(&mut self, winit_event: WinitEvent) {
         match winit_event {
-            smithay::backend::winit::WinitEvent::Resized {
-                size,
-                scale_factor: _,
-            } => self.backend.output.change_current_state(
-                Some(output::Mode {
-                    size,
-                    refresh: REFRESH_RATE * 1000,
-                }),
-                None,
-                None,
-                None,
-            ),
+            smithay::backend::winit::WinitEvent::Resized { size, scale_factor } => {
+                self.backend.scale_factor = scale_factor;
+                let config = &self.common.comp.config;
+                self.backend.output.change_current_state(
+                    Some(output::Mode {
+                        size,
+                        refresh: config.refresh_rate(OUTPUT_NAME) * 1000,
+                    }),
+                    None,
+                    Some(config.scale(OUTPUT_NAME, scale_factor)),
+                    None,
+                );
+                // Layer surfaces are placed relative to the output size
+                smithay::desktop::layer_map_for_output(&self.backend.output).arrange();
+                self.common.comp.schedule_redraw();
+            }
             smithay::backend::winit::WinitEvent::Focus(_) => {}
             smithay::backend::winit::WinitEvent::Input(event) => {
                 self.process_input(crate::input::InputEvent::Basic(event));
@@ -138,88 +144,54 @@ impl WinitApp {
             smithay::backend::winit::WinitEvent::CloseRequested => {
                 self.common.comp.loop_signal.stop()
             }
-            smithay::backend::winit::WinitEvent::Redraw => self.render(),
+            // The window was exposed or damaged
+            smithay::backend::winit::WinitEvent::Redraw => self.common.comp.schedule_redraw(),
         }
     }
 
     fn render(&mut self) {
-        let win_size = self.backend.winit.window_size();
-        let win_rect =
-            Rectangle::<_, Physical>::from_loc_and_size((0, 0), (win_size.w, win_size.h));
-
-        let iced_dmabuf = self.common.shell_driver.render(
-            &self.common.comp,
-            (win_size.w as u32, win_size.h as u32).into(),
+        let elements = crate::render::output_elements(
+            self.backend.winit.renderer(),
+            &self.backend.output,
+            &mut self.common,
         );
 
-        let iced_texture = self
-            .backend
-            .winit
-            .renderer()
-            .import_dmabuf(
-                &iced_dmabuf,
-                Some(&[win_rect.to_logical(1).to_buffer(
-                    1,
-                    Transform::Normal,
-                    &win_size.to_logical(1),
-                )]),
-            )
-            .expect("Cant import iced dmabuf into gles");
-
+        // The age tells how many frames ago the back buffer was drawn, so only what
+        // changed since then has to be drawn again
+        let age = self.backend.winit.buffer_age().unwrap_or(0);
         self.backend.winit.bind().expect("Unable to bind backend");
 
-        let space_render_elements = self
-            .common
-            .comp
-            .space
-            .render_elements_for_output(self.backend.winit.renderer(), &self.backend.output, 1.0)
-            .expect("Unable to get space render elements");
-
-        self.backend
+        let result = self
+            .backend
             .damage_tracker
             .render_output(
                 self.backend.winit.renderer(),
-                0,
-                &space_render_elements,
-                [1., 0., 1., 1.],
+                age,
+                &elements,
+                self.common.comp.config.theme.background,
             )
-            .expect("Unable to render space");
+            .expect("Unable to render output");
+
+        let mut feedback =
+            crate::render::post_repaint(&self.common, &self.backend.output, &result.states);
+
+        // Nothing changed, so the front buffer still shows the current frame
+        // and the feedback is discarded when dropped
+        if let Some(damage) = result.damage {
+            self.backend
+                .winit
+                .submit(Some(damage.as_slice()))
+                .expect("Unable to submit back buffer");
+
+            feedback.presented(
+                self.common.comp.clock.now(),
+                crate::render::refresh(&self.backend.output),
+                0,
+                // The host compositor does not tell when the frame was actually shown
+                wp_presentation_feedback::Kind::empty(),
+            );
+        }
 
-        let mut frame = self
-            .backend
-            .winit
-            .renderer()
-            .render(win_size, Transform::Flipped180)
-            .expect("Unable to create render frame");
-
-        frame
-            .render_texture_at(
-                &iced_texture,
-                (0, 0).into(),
-                1,
-                1.0,
-                Transform::Normal,
-                &[win_rect],
-                &[],
-                1.0,
-            )
-            .expect("Unable to render iced texture");
-
-        drop(frame);
-
-        self.backend
-            .winit
-            .submit(Some(&[win_rect]))
-            .expect("Unable to submit back buffer");
-
-        self.common.comp.space.elements().for_each(|window| {
-            // TODO this *should* only be run for visible surfaces
-            window.send_frame(
-                &self.backend.output,
-                self.common.comp.start_time.elapsed(),
-                Some(std::time::Duration::ZERO),
-                |_, _| Some(self.backend.output.clone()),
-            )
-        });
+        crate::render::send_preferred_scales(&self.common.comp, &self.backend.output);
     }
 }
@@ -0,0 +1,426 @@
+use chadland::ipc::protocol::{
+    Action, Event, Reply, ReplyMessage, Request, RequestMessage, Response, WindowInfo, VERSION,
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage: chadctl [options] <command>
+
+Commands:
+    windows                       List the windows
+    outputs                       List the outputs
+    workspaces                    List the workspaces
+    focused                       Show the focused window
+    subscribe                     Print window events until the compositor exits
+    spawn <command>               Run a shell command
+    spawn <program> <arg>...      Run the program with the arguments
+    focus <window>                Focus the window with the id
+    focus-next | focus-previous   Cycle the focus
+    close [window]                Close the window, or the focused one
+    toggle-floating [window]
+    toggle-fullscreen [window]
+    toggle-maximize [window]
+    minimize [window]
+    switch-workspace <number>
+    move <number> [window]        Move the window to the workspace
+    next-layout
+    reload-config
+    quit
+
+Options:
+    --json       Print the replies of the compositor as JSON lines
+    -h, --help   Print this help
+
+Options are only recognized before the command";
+
+/// Why chadctl failed, determining its exit code
+enum Error {
+    /// The command line could not be understood
+    Usage(String),
+    /// Talking to the compositor failed or it reported an error
+    Failed(String),
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Arguments of the command may look like options, like those of a spawned program
+    let mut json = false;
+    let mut command = 0;
+    for arg in &args {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            "--" => {
+                command += 1;
+                break;
+            }
+            option if option.starts_with('-') => {
+                eprintln!("chadctl: Unknown option {option:?}\n\n{USAGE}");
+                return ExitCode::from(2);
+            }
+            _ => break,
+        }
+        command += 1;
+    }
+
+    match run(&args[command..], json) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Error::Usage(message)) => {
+            eprintln!("chadctl: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(Error::Failed(message)) => {
+            eprintln!("chadctl: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String], json: bool) -> Result<(), Error> {
+    let request = parse_request(args)?;
+    let subscribe = request == Request::Subscribe;
+
+    let mut connection = Connection::open()?;
+    connection.send(request)?;
+
+    let response = match connection.receive()? {
+        Some(Reply::Response(response)) => response,
+        Some(Reply::Event(_)) => {
+            return Err(Error::Failed("Expected a response, got an event".into()))
+        }
+        None => return Err(Error::Failed("The compositor closed the connection".into())),
+    };
+    if let Response::Error { message } = &response {
+        return Err(Error::Failed(message.clone()));
+    }
+
+    if !subscribe {
+        print_response(&response, json);
+        return Ok(());
+    }
+
+    // The stream ends when the compositor exits
+    while let Some(reply) = connection.receive()? {
+        match reply {
+            Reply::Event(event) if json => print_json(&event),
+            Reply::Event(event) => print_event(&event),
+            Reply::Response(_) => {}
+        }
+        // Events are usually piped into other programs that wait for whole lines
+        let _ = std::io::stdout().flush();
+    }
+
+    Ok(())
+}
+
+fn parse_request(args: &[String]) -> Result<Request, Error> {
+    let Some((command, rest)) = args.split_first() else {
+        return Err(Error::Usage("Missing command".into()));
+    };
+
+    let window = |index: usize| -> Result<Option<u64>, Error> {
+        rest.get(index)
+            .map(|id| {
+                id.parse()
+                    .map_err(|_| Error::Usage(format!("Invalid window id {id:?}")))
+            })
+            .transpose()
+    };
+    let number = |index: usize, what: &str| -> Result<usize, Error> {
+        let value = rest
+            .get(index)
+            .ok_or_else(|| Error::Usage(format!("Missing {what}")))?;
+        value
+            .parse()
+            .map_err(|_| Error::Usage(format!("Invalid {what} {value:?}")))
+    };
+
+    let action = match command.as_str() {
+        "windows" => return Ok(Request::Windows),
+        "outputs" => return Ok(Request::Outputs),
+        "workspaces" => return Ok(Request::Workspaces),
+        "focused" => return Ok(Request::Focused),
+        "subscribe" => return Ok(Request::Subscribe),
+        "spawn" if rest.is_empty() => return Err(Error::Usage("Missing command to spawn".into())),
+        // A single argument is a shell command, more are a program and its arguments
+        "spawn" => Action::Spawn {
+            command: match rest {
+                [command] => command.clone(),
+                _ => rest
+                    .iter()
+                    .map(|arg| shell_quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            },
+        },
+        "focus" => Action::Focus {
+            window: window(0)?.ok_or_else(|| Error::Usage("Missing window id".into()))?,
+        },
+        "focus-next" => Action::FocusNext,
+        "focus-previous" => Action::FocusPrevious,
+        "close" => Action::Close { window: window(0)? },
+        "toggle-floating" => Action::ToggleFloating { window: window(0)? },
+        "toggle-fullscreen" => Action::ToggleFullscreen { window: window(0)? },
+        "toggle-maximize" => Action::ToggleMaximize { window: window(0)? },
+        "minimize" => Action::Minimize { window: window(0)? },
+        "switch-workspace" => Action::SwitchWorkspace {
+            workspace: number(0, "workspace")?,
+        },
+        "move" => Action::Move {
+            workspace: number(0, "workspace")?,
+            window: window(1)?,
+        },
+        "next-layout" => Action::NextLayout,
+        "reload-config" => Action::ReloadConfig,
+        "quit" => Action::Quit,
+        other => return Err(Error::Usage(format!("Unknown command {other:?}"))),
+    };
+
+    Ok(Request::Action { action })
+}
+
+/// Quotes the argument so the shell passes it on unchanged
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Line based connection to the IPC socket of the compositor
+struct Connection {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Connection {
+    fn open() -> Result<Self, Error> {
+        let path = std::env::var_os(chadland::ipc::protocol::SOCKET_ENV)
+            .map(Into::into)
+            .or_else(chadland::ipc::socket_path)
+            .ok_or_else(|| Error::Failed("Unable to find the compositor socket".into()))?;
+
+        let writer = UnixStream::connect(&path)
+            .map_err(|err| Error::Failed(format!("Unable to connect to {path:?}: {err}")))?;
+        let reader = writer
+            .try_clone()
+            .map(BufReader::new)
+            .map_err(|err| Error::Failed(format!("Unable to read from {path:?}: {err}")))?;
+
+        Ok(Self { reader, writer })
+    }
+
+    fn send(&mut self, request: Request) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(&RequestMessage {
+            version: VERSION,
+            request,
+        })
+        .map_err(|err| Error::Failed(format!("Unable to serialize the request: {err}")))?;
+        line.push(b'\n');
+
+        self.writer
+            .write_all(&line)
+            .map_err(|err| Error::Failed(format!("Unable to send the request: {err}")))
+    }
+
+    /// Waits for the next reply. Returns [None] once the compositor closed the connection
+    fn receive(&mut self) -> Result<Option<Reply>, Error> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => return Ok(None),
+            Ok(_) => {}
+            Err(err) => return Err(Error::Failed(format!("Unable to read the reply: {err}"))),
+        }
+
+        let message: ReplyMessage = serde_json::from_str(&line)
+            .map_err(|err| Error::Failed(format!("Invalid reply {line:?}: {err}")))?;
+        if message.version != VERSION {
+            return Err(Error::Failed(format!(
+                "The compositor speaks protocol version {}, expected {VERSION}",
+                message.version
+            )));
+        }
+
+        Ok(Some(message.reply))
+    }
+}
+
+fn print_json(value: &impl serde::Serialize) {
+    println!(
+        "{}",
+        serde_json::to_string(value).expect("Unable to serialize reply")
+    );
+}
+
+fn print_response(response: &Response, json: bool) {
+    if json {
+        return print_json(response);
+    }
+
+    match response {
+        Response::Windows { windows } => print_windows(windows),
+        Response::Focused { window } => print_windows(window.as_slice()),
+        Response::Outputs { outputs } => print_table(
+            &["NAME", "GEOMETRY", "REFRESH", "SCALE", "WORKSPACE"],
+            outputs
+                .iter()
+                .map(|output| {
+                    vec![
+                        output.name.clone(),
+                        output
+                            .geometry
+                            .map(|g| format!("{}x{}+{}+{}", g.width, g.height, g.x, g.y))
+                            .unwrap_or_default(),
+                        output
+                            .refresh
+                            .map(|refresh| format!("{:.2} Hz", refresh as f64 / 1000.))
+                            .unwrap_or_default(),
+                        output.scale.to_string(),
+                        output.workspace.to_string(),
+                    ]
+                })
+                .collect(),
+        ),
+        Response::Workspaces { workspaces } => print_table(
+            &["NUMBER", "OUTPUT", "LAYOUT", "WINDOWS"],
+            workspaces
+                .iter()
+                .map(|workspace| {
+                    vec![
+                        workspace.number.to_string(),
+                        workspace.output.clone().unwrap_or_default(),
+                        workspace.layout.clone(),
+                        workspace
+                            .windows
+                            .iter()
+                            .map(u64::to_string)
+                            .collect::<Vec<_>>()
+                            .join(","),
+                    ]
+                })
+                .collect(),
+        ),
+        Response::Done | Response::Subscribed | Response::Error { .. } => {}
+    }
+}
+
+fn print_windows(windows: &[WindowInfo]) {
+    print_table(
+        &["ID", "WORKSPACE", "STATE", "APP ID", "TITLE"],
+        windows
+            .iter()
+            .map(|window| {
+                vec![
+                    window.id.to_string(),
+                    window
+                        .workspace
+                        .map(|number| number.to_string())
+                        .unwrap_or_default(),
+                    window_state(window),
+                    window.app_id.clone().unwrap_or_default(),
+                    window.title.clone().unwrap_or_default(),
+                ]
+            })
+            .collect(),
+    );
+}
+
+/// Short description of how the window is shown, like `focused,floating`
+fn window_state(window: &WindowInfo) -> String {
+    let states = [
+        (window.focused, "focused"),
+        (window.floating, "floating"),
+        (window.fullscreen, "fullscreen"),
+        (window.maximized, "maximized"),
+        (window.minimized, "minimized"),
+    ];
+    let state: Vec<_> = states
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect();
+
+    if state.is_empty() {
+        "tiled".to_string()
+    } else {
+        state.join(",")
+    }
+}
+
+fn print_event(event: &Event) {
+    match event {
+        Event::WindowOpened { window } => println!(
+            "opened {} {:?} {:?}",
+            window.id,
+            window.app_id.as_deref().unwrap_or_default(),
+            window.title.as_deref().unwrap_or_default()
+        ),
+        Event::WindowClosed { id } => println!("closed {id}"),
+        Event::WindowFocused {
+            window: Some(window),
+        } => println!("focused {}", window.id),
+        Event::WindowFocused { window: None } => println!("focused none"),
+    }
+}
+
+/// Prints the rows with left aligned columns
+fn print_table(header: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = header.iter().map(|title| title.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(|title| title.to_string()).collect();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawned(args: &[&str]) -> String {
+        let args: Vec<String> = ["spawn"]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .collect();
+        match parse_request(&args) {
+            Ok(Request::Action {
+                action: Action::Spawn { command },
+            }) => command,
+            _ => panic!("Expected a spawn request for {args:?}"),
+        }
+    }
+
+    #[test]
+    fn single_spawn_argument_is_a_shell_command() {
+        assert_eq!(spawned(&["foot -e htop"]), "foot -e htop");
+    }
+
+    #[test]
+    fn spawn_arguments_keep_their_quoting() {
+        assert_eq!(spawned(&["foot", "-e", "htop"]), "foot -e htop");
+        assert_eq!(
+            spawned(&["notify-send", "hello world", "it's $HOME", ""]),
+            r"notify-send 'hello world' 'it'\''s $HOME' ''"
+        );
+    }
+}
@@ -0,0 +1,704 @@
+use crate::{
+    action::{Action, Target},
+    keybindings::{KeyChord, Modifiers},
+};
+use serde::Deserialize;
+use smithay::{
+    input::keyboard::{xkb, Keysym, XkbConfig},
+    reexports::calloop,
+};
+use std::{
+    collections::HashMap, os::unix::fs::MetadataExt, path::PathBuf, time::Duration,
+    time::SystemTime,
+};
+use tracing::{error, info};
+
+/// Refresh rate of outputs without a configured one
+pub const DEFAULT_REFRESH_RATE: i32 = 60;
+/// Highest refresh rate that can be configured. Frames are scheduled in whole milliseconds
+const MAX_REFRESH_RATE: i32 = 1000;
+/// How often the configuration file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Contents of `$XDG_CONFIG_HOME/chadland/config.toml`.
+/// Missing sections and values use their defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub input: Input,
+    /// Settings of the outputs by their name
+    pub outputs: HashMap<String, OutputConfig>,
+    pub theme: Theme,
+    /// Replaces the default keybindings when present.
+    /// Maps chords like `"Logo+Shift+q"` to actions like `"close"` or `{ spawn = "foot" }`
+    pub keybindings: Option<HashMap<String, BoundAction>>,
+    /// Shell commands run once when the compositor starts
+    pub autostart: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Input {
+    pub keyboard: Keyboard,
+}
+
+/// Keymap and repeat settings. Empty xkb names use the system defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keyboard {
+    pub rules: String,
+    pub model: String,
+    pub layout: String,
+    pub variant: String,
+    pub options: Option<String>,
+    /// Milliseconds a key has to be held before it repeats
+    pub repeat_delay: i32,
+    /// Repeats per second
+    pub repeat_rate: i32,
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self {
+            rules: String::new(),
+            model: String::new(),
+            layout: String::new(),
+            variant: String::new(),
+            options: None,
+            repeat_delay: 500,
+            repeat_rate: 100,
+        }
+    }
+}
+
+impl Keyboard {
+    pub fn xkb_config(&self) -> XkbConfig<'_> {
+        XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout: &self.layout,
+            variant: &self.variant,
+            options: self.options.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Refresh rate in Hz
+    pub refresh_rate: Option<i32>,
+    /// Location of the top left corner in the global space
+    pub position: Option<(i32, i32)>,
+    /// Scale factor like `2` or `1.5`. Defaults to what the backend reports
+    pub scale: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Name of a built-in iced theme like `"Catppuccin Mocha"`
+    pub name: String,
+    pub font_size: f32,
+    /// RGBA color of the areas not covered by anything
+    pub background: [f32; 4],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: crate::iced::Theme::CatppuccinMocha.to_string(),
+            font_size: 16.,
+            background: [0.4, 0.4, 0.667, 1.],
+        }
+    }
+}
+
+impl Theme {
+    /// The iced theme with the configured name
+    pub fn iced_theme(&self) -> Option<crate::iced::Theme> {
+        crate::iced::Theme::ALL
+            .iter()
+            .find(|theme| theme.to_string() == self.name)
+            .cloned()
+    }
+}
+
+/// An [Action] that can be written in the configuration.
+/// Actions on windows apply to the focused one
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundAction {
+    Spawn(String),
+    FocusNext,
+    FocusPrevious,
+    Close,
+    ToggleFloating,
+    ToggleFullscreen,
+    ToggleMaximize,
+    Minimize,
+    SwitchWorkspace(usize),
+    MoveToWorkspace(usize),
+    SetLayout(String),
+    NextLayout,
+    AdjustMasterRatio(i32),
+    AdjustGaps(i32),
+    Quit,
+    ReloadConfig,
+}
+
+impl BoundAction {
+    fn action(&self) -> Result<Action, String> {
+        Ok(match self.clone() {
+            Self::Spawn(command) => Action::Spawn(command),
+            Self::FocusNext => Action::FocusNext,
+            Self::FocusPrevious => Action::FocusPrevious,
+            Self::Close => Action::Close(Target::Focused),
+            Self::ToggleFloating => Action::ToggleFloating(Target::Focused),
+            Self::ToggleFullscreen => Action::ToggleFullscreen(Target::Focused),
+            Self::ToggleMaximize => Action::ToggleMaximize(Target::Focused),
+            Self::Minimize => Action::Minimize(Target::Focused),
+            Self::SwitchWorkspace(number) => Action::SwitchWorkspace(number),
+            Self::MoveToWorkspace(number) => Action::MoveToWorkspace(Target::Focused, number),
+            Self::SetLayout(name) => Action::SetLayout(
+                crate::layout::LayoutKind::ALL
+                    .iter()
+                    .copied()
+                    .find(|kind| kind.layout().name().eq_ignore_ascii_case(&name))
+                    .ok_or_else(|| format!("unknown layout {name:?}"))?,
+            ),
+            Self::NextLayout => Action::NextLayout,
+            Self::AdjustMasterRatio(percent) => Action::AdjustMasterRatio(percent),
+            Self::AdjustGaps(delta) => Action::AdjustGaps(delta),
+            Self::Quit => Action::Quit,
+            Self::ReloadConfig => Action::ReloadConfig,
+        })
+    }
+}
+
+/// Why a configuration file was rejected
+#[derive(Debug)]
+pub enum Error {
+    Read(std::io::Error),
+    Parse(toml::de::Error),
+    /// The file is valid TOML but some values make no sense
+    Invalid(Vec<String>),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(err) => write!(f, "Unable to read the file: {err}"),
+            Self::Parse(err) => write!(f, "{err}"),
+            Self::Invalid(problems) => {
+                for problem in problems {
+                    writeln!(f, "- {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Location of the configuration file
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("chadland").join("config.toml"))
+    }
+
+    /// Reads and validates the configuration file. A missing file results in the defaults
+    pub fn load() -> Result<Self, Error> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Read(err)),
+        };
+
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        let config: Self = toml::from_str(contents).map_err(Error::Parse)?;
+
+        let mut problems = Vec::new();
+        config.validate(&mut problems);
+        if let Err(bindings_problems) = config.bindings() {
+            problems.extend(bindings_problems);
+        }
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(Error::Invalid(problems))
+        }
+    }
+
+    /// Loads the configuration, falling back to the defaults if it is invalid
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|err| {
+            error!("Invalid configuration, using the defaults instead:\n{err}");
+            Self::default()
+        })
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        let keyboard = &self.input.keyboard;
+        if keyboard.repeat_delay < 0 {
+            problems.push("input.keyboard.repeat_delay must not be negative".to_string());
+        }
+        if keyboard.repeat_rate < 0 {
+            problems.push("input.keyboard.repeat_rate must not be negative".to_string());
+        }
+
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        if xkb::Keymap::new_from_names(
+            &context,
+            &keyboard.rules,
+            &keyboard.model,
+            &keyboard.layout,
+            &keyboard.variant,
+            keyboard.options.clone(),
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .is_none()
+        {
+            problems.push(format!(
+                "input.keyboard does not describe a valid keymap (layout {:?}, variant {:?})",
+                keyboard.layout, keyboard.variant
+            ));
+        }
+
+        for (name, output) in &self.outputs {
+            if output
+                .refresh_rate
+                .is_some_and(|rate| !(1..=MAX_REFRESH_RATE).contains(&rate))
+            {
+                problems.push(format!(
+                    "outputs.{name}.refresh_rate must be between 1 and {MAX_REFRESH_RATE}"
+                ));
+            }
+            if output
+                .scale
+                .is_some_and(|scale| !scale.is_finite() || scale <= 0.)
+            {
+                problems.push(format!("outputs.{name}.scale must be positive"));
+            }
+        }
+
+        if self.theme.iced_theme().is_none() {
+            let names: Vec<_> = crate::iced::Theme::ALL
+                .iter()
+                .map(|theme| format!("{:?}", theme.to_string()))
+                .collect();
+            problems.push(format!(
+                "theme.name {:?} is unknown, expected one of {}",
+                self.theme.name,
+                names.join(", ")
+            ));
+        }
+        if !self.theme.font_size.is_finite() || self.theme.font_size <= 0. {
+            problems.push("theme.font_size must be positive".to_string());
+        }
+        if self
+            .theme
+            .background
+            .iter()
+            .any(|channel| !(0. ..=1.).contains(channel))
+        {
+            problems.push("theme.background channels must be between 0 and 1".to_string());
+        }
+    }
+
+    /// The configured keybindings, or the defaults if there are none
+    pub fn bindings(&self) -> Result<HashMap<KeyChord, Action>, Vec<String>> {
+        let Some(keybindings) = &self.keybindings else {
+            return Ok(crate::keybindings::Keybindings::default().bindings);
+        };
+
+        let mut bindings = HashMap::new();
+        let mut problems = Vec::new();
+        for (chord, action) in keybindings {
+            match (parse_chord(chord), action.action()) {
+                (Ok(chord), Ok(action)) => {
+                    bindings.insert(chord, action);
+                }
+                (Err(problem), _) | (_, Err(problem)) => {
+                    problems.push(format!("keybindings.{chord:?}: {problem}"))
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(bindings)
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Refresh rate of the output in Hz
+    pub fn refresh_rate(&self, output_name: &str) -> i32 {
+        self.outputs
+            .get(output_name)
+            .and_then(|output| output.refresh_rate)
+            .unwrap_or(DEFAULT_REFRESH_RATE)
+    }
+
+    /// Scale of the output, falling back to the scale reported by the backend
+    pub fn scale(&self, output_name: &str, native: f64) -> smithay::output::Scale {
+        let scale = self
+            .outputs
+            .get(output_name)
+            .and_then(|output| output.scale)
+            .unwrap_or(native);
+
+        // Clients can render integer scales without the fractional scale protocol
+        if scale.fract() == 0. {
+            smithay::output::Scale::Integer(scale as i32)
+        } else {
+            smithay::output::Scale::Fractional(scale)
+        }
+    }
+
+    /// Time between two frames on the output
+    pub fn frame_duration(&self, output_name: &str) -> Duration {
+        Duration::from_millis(1000 / self.refresh_rate(output_name) as u64)
+    }
+}
+
+/// Parses chords like `"Logo+Shift+Return"`. The key is named after its unshifted keysym
+fn parse_chord(chord: &str) -> Result<KeyChord, String> {
+    let mut parts: Vec<_> = chord.split('+').map(str::trim).collect();
+    let key = parts
+        .pop()
+        .filter(|key| !key.is_empty())
+        .ok_or("missing key")?;
+
+    let mut modifiers = Modifiers::default();
+    for modifier in parts {
+        match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => modifiers.ctrl = true,
+            "alt" => modifiers.alt = true,
+            "shift" => modifiers.shift = true,
+            "logo" | "super" | "mod4" => modifiers.logo = true,
+            _ => return Err(format!("unknown modifier {modifier:?}")),
+        }
+    }
+
+    let keysym = xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE);
+    if keysym == Keysym::NoSymbol {
+        return Err(format!("unknown key {key:?}"));
+    }
+
+    Ok(KeyChord::new(modifiers, keysym))
+}
+
+/// Identifies the contents of the configuration file. Replacing the file changes the inode,
+/// and writing it changes the change time, even when the modification time is kept
+#[derive(Debug, PartialEq, Eq)]
+struct FileVersion {
+    device: u64,
+    inode: u64,
+    len: u64,
+    modified: Option<SystemTime>,
+    changed: (i64, i64),
+}
+
+impl FileVersion {
+    fn of(path: &Option<PathBuf>) -> Option<Self> {
+        let metadata = std::fs::metadata(path.as_ref()?).ok()?;
+        Some(Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            changed: (metadata.ctime(), metadata.ctime_nsec()),
+        })
+    }
+}
+
+/// Reloads the configuration whenever the file is written, replaced, created or removed
+pub fn init_watcher<B: crate::Backend>(loop_handle: &calloop::LoopHandle<'static, crate::App<B>>) {
+    let path = Config::path();
+    let mut last_version = FileVersion::of(&path);
+
+    loop_handle
+        .insert_source(
+            calloop::timer::Timer::from_duration(WATCH_INTERVAL),
+            move |_, _, app| {
+                let current = FileVersion::of(&path);
+                if current != last_version {
+                    last_version = current;
+                    app.run_action(Action::ReloadConfig);
+                }
+                calloop::timer::TimeoutAction::ToDuration(WATCH_INTERVAL)
+            },
+        )
+        .expect("Unable to insert config watcher source");
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    /// Loads the configuration file again and applies it if it is valid
+    pub fn reload_config(&mut self) {
+        match Config::load() {
+            Ok(config) => {
+                info!("Reloaded configuration");
+                self.apply_config(config);
+            }
+            Err(err) => error!("Invalid configuration, keeping the previous one:\n{err}"),
+        }
+    }
+
+    /// Makes the configuration take effect. It has to be valid
+    pub fn apply_config(&mut self, config: Config) {
+        if let Some(keyboard) = self.common.comp.seat.get_keyboard() {
+            let settings = &config.input.keyboard;
+            if let Err(err) = keyboard.set_xkb_config(self, settings.xkb_config()) {
+                error!("Unable to apply the keymap: {err:?}");
+            }
+            keyboard.change_repeat_info(settings.repeat_rate, settings.repeat_delay);
+        }
+
+        let comp = &mut self.common.comp;
+        if let Ok(bindings) = config.bindings() {
+            comp.keybindings.bindings = bindings;
+        }
+
+        let outputs: Vec<_> = comp.space.outputs().cloned().collect();
+        for output in outputs {
+            let mode = output.current_mode().map(|mode| smithay::output::Mode {
+                refresh: config.refresh_rate(&output.name()) * 1000,
+                ..mode
+            });
+            let position = config
+                .outputs
+                .get(&output.name())
+                .and_then(|settings| settings.position)
+                .map(smithay::utils::Point::from);
+
+            let scale = config.scale(&output.name(), self.backend.native_scale(&output));
+
+            output.change_current_state(mode, None, Some(scale), position);
+            if let Some(position) = position {
+                comp.space.map_output(&output, position);
+            }
+            // Layer surfaces are placed relative to the logical output size
+            smithay::desktop::layer_map_for_output(&output).arrange();
+        }
+
+        if let Some(theme) = config.theme.iced_theme() {
+            self.common
+                .shell_driver
+                .set_theme(theme, config.theme.font_size);
+        }
+
+        self.common.comp.config = config;
+        self.common.comp.schedule_redraw();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifiers(ctrl: bool, alt: bool, shift: bool, logo: bool) -> Modifiers {
+        Modifiers {
+            ctrl,
+            alt,
+            shift,
+            logo,
+        }
+    }
+
+    /// The problems of a configuration that parses but is invalid
+    fn problems(contents: &str) -> Vec<String> {
+        match Config::parse(contents) {
+            Err(Error::Invalid(problems)) => problems,
+            other => panic!("Expected invalid values, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn chords_with_modifiers() {
+        assert_eq!(
+            parse_chord("Logo+Shift+q"),
+            Ok(KeyChord::new(
+                modifiers(false, false, true, true),
+                Keysym::q
+            ))
+        );
+        assert_eq!(
+            parse_chord("ctrl + Alt + Return"),
+            Ok(KeyChord::new(
+                modifiers(true, true, false, false),
+                Keysym::Return
+            ))
+        );
+        assert_eq!(
+            parse_chord("Super+Mod4+Control+F1"),
+            Ok(KeyChord::new(
+                modifiers(true, false, false, true),
+                Keysym::F1
+            ))
+        );
+    }
+
+    #[test]
+    fn chords_without_modifiers() {
+        assert_eq!(
+            parse_chord("Escape"),
+            Ok(KeyChord::new(Modifiers::default(), Keysym::Escape))
+        );
+    }
+
+    #[test]
+    fn invalid_chords() {
+        assert_eq!(parse_chord(""), Err("missing key".to_string()));
+        assert_eq!(parse_chord("Logo+"), Err("missing key".to_string()));
+        assert_eq!(
+            parse_chord("Hyper+q"),
+            Err("unknown modifier \"Hyper\"".to_string())
+        );
+        assert_eq!(
+            parse_chord("Logo+NoSuchKey"),
+            Err("unknown key \"NoSuchKey\"".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(matches!(
+            Config::parse("unknown = true"),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            Config::parse("[theme]\nfont = 12.0"),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            Config::parse("[outputs.DP-1]\nmode = \"1920x1080\""),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_actions_are_rejected() {
+        assert!(matches!(
+            Config::parse("[keybindings]\n\"Logo+q\" = \"explode\""),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_theme_is_reported() {
+        let problems = problems(
+            "[theme]\nname = \"Nope\"\nfont_size = 0.0\nbackground = [0.0, 0.0, 2.0, 1.0]",
+        );
+        for expected in [
+            "theme.name \"Nope\" is unknown",
+            "theme.font_size must be positive",
+            "theme.background channels must be between 0 and 1",
+        ] {
+            assert!(
+                problems.iter().any(|problem| problem.starts_with(expected)),
+                "{expected:?} missing from {problems:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_scale_is_reported() {
+        for scale in ["0.0", "-1.5", "nan"] {
+            let problems = problems(&format!("[outputs.DP-1]\nscale = {scale}"));
+            assert!(
+                problems.contains(&"outputs.DP-1.scale must be positive".to_string()),
+                "{problems:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_refresh_rate_is_reported() {
+        for rate in ["0", "-60", "1001", "2147483647"] {
+            let problems = problems(&format!("[outputs.DP-1]\nrefresh_rate = {rate}"));
+            assert!(
+                problems
+                    .contains(&"outputs.DP-1.refresh_rate must be between 1 and 1000".to_string()),
+                "{problems:?}"
+            );
+        }
+        if let Err(Error::Invalid(problems)) = Config::parse("[outputs.DP-1]\nrefresh_rate = 1000")
+        {
+            assert!(
+                !problems
+                    .iter()
+                    .any(|problem| problem.contains("refresh_rate")),
+                "{problems:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_keybindings_are_reported() {
+        let problems = problems(
+            "[keybindings]\n\"Hyper+q\" = \"close\"\n\"Logo+l\" = { set_layout = \"Nope\" }",
+        );
+        let keybinding_problems = problems
+            .iter()
+            .filter(|problem| problem.starts_with("keybindings."))
+            .count();
+        assert_eq!(keybinding_problems, 2, "{problems:?}");
+    }
+
+    #[test]
+    fn integer_scales_stay_integer() {
+        let config = Config::default();
+        assert!(matches!(
+            config.scale("DP-1", 2.),
+            smithay::output::Scale::Integer(2)
+        ));
+        assert!(matches!(
+            config.scale("DP-1", 1.5),
+            smithay::output::Scale::Fractional(scale) if scale == 1.5
+        ));
+    }
+
+    #[test]
+    fn configured_scale_overrides_the_native_one() {
+        let mut config = Config::default();
+        config.outputs.insert(
+            "DP-1".to_string(),
+            OutputConfig {
+                scale: Some(1.25),
+                ..OutputConfig::default()
+            },
+        );
+        config.outputs.insert(
+            "DP-2".to_string(),
+            OutputConfig {
+                scale: Some(3.),
+                ..OutputConfig::default()
+            },
+        );
+
+        assert!(matches!(
+            config.scale("DP-1", 1.),
+            smithay::output::Scale::Fractional(scale) if scale == 1.25
+        ));
+        assert!(matches!(
+            config.scale("DP-2", 1.),
+            smithay::output::Scale::Integer(3)
+        ));
+        assert!(matches!(
+            config.scale("HDMI-1", 1.),
+            smithay::output::Scale::Integer(1)
+        ));
+    }
+}
@@ -0,0 +1,507 @@
+use crate::layout::Geometry;
+use smithay::{
+    desktop,
+    input::pointer::{
+        AxisFrame, ButtonEvent, Focus, GestureHoldBeginEvent, GestureHoldEndEvent,
+        GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+        GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData,
+        MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
+    },
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    utils::{Logical, Point, Serial, Size},
+};
+use std::sync::Mutex;
+
+/// Implements the [PointerGrab] methods that just forward the events without any focus
+macro_rules! forward_pointer_events {
+    () => {
+        fn relative_motion(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            _focus: Option<(WlSurface, Point<f64, Logical>)>,
+            event: &RelativeMotionEvent,
+        ) {
+            handle.relative_motion(data, None, event);
+        }
+
+        fn axis(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            details: AxisFrame,
+        ) {
+            handle.axis(data, details);
+        }
+
+        fn frame(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+        ) {
+            handle.frame(data);
+        }
+
+        fn gesture_swipe_begin(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GestureSwipeBeginEvent,
+        ) {
+            handle.gesture_swipe_begin(data, event);
+        }
+
+        fn gesture_swipe_update(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GestureSwipeUpdateEvent,
+        ) {
+            handle.gesture_swipe_update(data, event);
+        }
+
+        fn gesture_swipe_end(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GestureSwipeEndEvent,
+        ) {
+            handle.gesture_swipe_end(data, event);
+        }
+
+        fn gesture_pinch_begin(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GesturePinchBeginEvent,
+        ) {
+            handle.gesture_pinch_begin(data, event);
+        }
+
+        fn gesture_pinch_update(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GesturePinchUpdateEvent,
+        ) {
+            handle.gesture_pinch_update(data, event);
+        }
+
+        fn gesture_pinch_end(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GesturePinchEndEvent,
+        ) {
+            handle.gesture_pinch_end(data, event);
+        }
+
+        fn gesture_hold_begin(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GestureHoldBeginEvent,
+        ) {
+            handle.gesture_hold_begin(data, event);
+        }
+
+        fn gesture_hold_end(
+            &mut self,
+            data: &mut crate::App<B>,
+            handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+            event: &GestureHoldEndEvent,
+        ) {
+            handle.gesture_hold_end(data, event);
+        }
+
+        fn start_data(&self) -> &GrabStartData<crate::App<B>> {
+            &self.start_data
+        }
+    };
+}
+
+/// Drags a window until the button is released.
+/// Floating windows follow the pointer, tiled windows swap
+/// their places in the layout with the window they are dropped onto
+pub struct MoveGrab<B: crate::Backend> {
+    pub start_data: GrabStartData<crate::App<B>>,
+    pub window: desktop::Window,
+    /// Location of the floating window when the grab started
+    pub initial_location: Option<Point<i32, Logical>>,
+}
+
+impl<B: crate::Backend> PointerGrab<crate::App<B>> for MoveGrab<B> {
+    fn motion(
+        &mut self,
+        data: &mut crate::App<B>,
+        handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+        _focus: Option<(WlSurface, Point<f64, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // Clients do not get pointer input while a window is dragged
+        handle.motion(data, None, event);
+
+        if let Some(initial_location) = self.initial_location {
+            let delta = (event.location - self.start_data.location).to_i32_round();
+            if let Some(floating) = data.common.comp.floating_mut(&self.window) {
+                floating.geometry.loc = initial_location + delta;
+            }
+        }
+    }
+
+    fn button(
+        &mut self,
+        data: &mut crate::App<B>,
+        handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+
+        // Grabs started from the shell hold a button the pointer does not know about
+        let button = self.start_data.button;
+        if handle.current_pressed().contains(&button)
+            || data.common.comp.shell_buttons.contains(&button)
+        {
+            return;
+        }
+
+        let comp = &mut data.common.comp;
+        let target = comp
+            .space
+            .element_under(comp.pointer_location)
+            .map(|(target, _)| target.clone());
+        if let (None, Some(target)) = (self.initial_location, target) {
+            let windows = &mut comp.workspace_mut().windows;
+            let dragged = windows.iter().position(|window| window == &self.window);
+            let target = windows.iter().position(|window| window == &target);
+            if let (Some(dragged), Some(target)) = (dragged, target) {
+                windows.swap(dragged, target);
+            }
+        }
+
+        handle.unset_grab(self, data, event.serial, event.time, true);
+    }
+
+    forward_pointer_events!();
+
+    fn unset(&mut self, _data: &mut crate::App<B>) {}
+}
+
+/// Edges of a window that are moved by a [ResizeGrab]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Edges {
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl From<xdg_toplevel::ResizeEdge> for Edges {
+    fn from(edge: xdg_toplevel::ResizeEdge) -> Self {
+        use xdg_toplevel::ResizeEdge;
+
+        Self {
+            top: matches!(
+                edge,
+                ResizeEdge::Top | ResizeEdge::TopLeft | ResizeEdge::TopRight
+            ),
+            bottom: matches!(
+                edge,
+                ResizeEdge::Bottom | ResizeEdge::BottomLeft | ResizeEdge::BottomRight
+            ),
+            left: matches!(
+                edge,
+                ResizeEdge::Left | ResizeEdge::TopLeft | ResizeEdge::BottomLeft
+            ),
+            right: matches!(
+                edge,
+                ResizeEdge::Right | ResizeEdge::TopRight | ResizeEdge::BottomRight
+            ),
+        }
+    }
+}
+
+impl From<smithay::xwayland::xwm::ResizeEdge> for Edges {
+    fn from(edge: smithay::xwayland::xwm::ResizeEdge) -> Self {
+        use smithay::xwayland::xwm::ResizeEdge;
+
+        let edge = match edge {
+            ResizeEdge::Top => xdg_toplevel::ResizeEdge::Top,
+            ResizeEdge::Bottom => xdg_toplevel::ResizeEdge::Bottom,
+            ResizeEdge::Left => xdg_toplevel::ResizeEdge::Left,
+            ResizeEdge::Right => xdg_toplevel::ResizeEdge::Right,
+            ResizeEdge::TopLeft => xdg_toplevel::ResizeEdge::TopLeft,
+            ResizeEdge::TopRight => xdg_toplevel::ResizeEdge::TopRight,
+            ResizeEdge::BottomLeft => xdg_toplevel::ResizeEdge::BottomLeft,
+            ResizeEdge::BottomRight => xdg_toplevel::ResizeEdge::BottomRight,
+        };
+        edge.into()
+    }
+}
+
+/// Edges of a window being resized that stay in place, stored in the user data of the window.
+/// Clients commit the new size after the grab requested it, so the window is moved when they do
+#[derive(Debug, Clone, Copy)]
+struct ResizeAnchor {
+    edges: Edges,
+    /// Right and bottom edges of the floating geometry when the grab started
+    bottom_right: Point<i32, Logical>,
+    decoration: Size<i32, Logical>,
+    /// The grab ended, but the client may still commit the last requested size
+    finished: bool,
+}
+
+type ResizeAnchorData = Mutex<Option<ResizeAnchor>>;
+
+fn resize_anchor(window: &desktop::Window) -> Option<ResizeAnchor> {
+    *window
+        .user_data()
+        .get::<ResizeAnchorData>()?
+        .lock()
+        .unwrap()
+}
+
+fn set_resize_anchor(window: &desktop::Window, anchor: Option<ResizeAnchor>) {
+    window
+        .user_data()
+        .insert_if_missing_threadsafe(ResizeAnchorData::default);
+    if let Some(data) = window.user_data().get::<ResizeAnchorData>() {
+        *data.lock().unwrap() = anchor;
+    }
+}
+
+/// Moves the floating window so the edges opposite to the resized ones stay where they were.
+/// Uses the size the client committed, as it may not match the requested one
+fn apply_resize_anchor<B: crate::Backend>(
+    comp: &mut crate::state::Compositor<B>,
+    window: &desktop::Window,
+    anchor: &ResizeAnchor,
+) {
+    let committed = window.geometry().size + anchor.decoration;
+    if let Some(floating) = comp.floating_mut(window) {
+        if anchor.edges.left {
+            floating.geometry.loc.x = anchor.bottom_right.x - committed.w;
+        }
+        if anchor.edges.top {
+            floating.geometry.loc.y = anchor.bottom_right.y - committed.h;
+        }
+    }
+}
+
+/// Keeps the anchored edges of a window being resized in place once the client committed a size
+pub fn handle_resize_commit<B: crate::Backend>(
+    comp: &mut crate::state::Compositor<B>,
+    window: &desktop::Window,
+) {
+    let Some(anchor) = resize_anchor(window) else {
+        return;
+    };
+    apply_resize_anchor(comp, window, &anchor);
+
+    // The resize is over once the client committed the configure without the resizing state
+    let resizing = window.toplevel().is_some_and(|toplevel| {
+        toplevel
+            .current_state()
+            .states
+            .contains(xdg_toplevel::State::Resizing)
+    });
+    if anchor.finished && !resizing {
+        set_resize_anchor(window, None);
+    }
+}
+
+/// Resizes a floating window by moving its edges with the pointer until the button is released.
+/// The opposite edges stay in place
+pub struct ResizeGrab<B: crate::Backend> {
+    pub start_data: GrabStartData<crate::App<B>>,
+    pub window: desktop::Window,
+    pub edges: Edges,
+    /// Floating geometry when the grab started
+    pub initial_geometry: Geometry,
+    /// Space around the client content taken by the title bar
+    pub decoration: Size<i32, Logical>,
+}
+
+impl<B: crate::Backend> PointerGrab<crate::App<B>> for ResizeGrab<B> {
+    fn motion(
+        &mut self,
+        data: &mut crate::App<B>,
+        handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+        _focus: Option<(WlSurface, Point<f64, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        handle.motion(data, None, event);
+
+        let delta = (event.location - self.start_data.location).to_i32_round::<i32>();
+        let (min, max) = crate::util::surface::size_hints(&self.window);
+        let initial = self.initial_geometry;
+
+        let width = resize_length(
+            initial.size.w,
+            match (self.edges.left, self.edges.right) {
+                (true, _) => -delta.x,
+                (_, true) => delta.x,
+                _ => 0,
+            },
+            self.decoration.w,
+            min.w,
+            max.w,
+        );
+        let height = resize_length(
+            initial.size.h,
+            match (self.edges.top, self.edges.bottom) {
+                (true, _) => -delta.y,
+                (_, true) => delta.y,
+                _ => 0,
+            },
+            self.decoration.h,
+            min.h,
+            max.h,
+        );
+
+        let comp = &mut data.common.comp;
+        if let Some(floating) = comp.floating_mut(&self.window) {
+            floating.geometry.size = (width, height).into();
+        }
+        if let Some(anchor) = resize_anchor(&self.window) {
+            apply_resize_anchor(comp, &self.window, &anchor);
+        }
+    }
+
+    fn button(
+        &mut self,
+        data: &mut crate::App<B>,
+        handle: &mut PointerInnerHandle<'_, crate::App<B>>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+
+        if !handle.current_pressed().contains(&self.start_data.button) {
+            handle.unset_grab(self, data, event.serial, event.time, true);
+        }
+    }
+
+    forward_pointer_events!();
+
+    fn unset(&mut self, _data: &mut crate::App<B>) {
+        let Some(toplevel) = self.window.toplevel() else {
+            // X11 windows are configured with the final size right away
+            set_resize_anchor(&self.window, None);
+            return;
+        };
+
+        set_resizing(toplevel, false);
+        if let Some(mut anchor) = resize_anchor(&self.window) {
+            anchor.finished = true;
+            set_resize_anchor(&self.window, Some(anchor));
+        }
+    }
+}
+
+/// Resizes one dimension of the window by the delta, keeping the client content within its
+/// size hints where zero means unbounded
+fn resize_length(initial: i32, delta: i32, decoration: i32, min: i32, max: i32) -> i32 {
+    let mut content = (initial + delta - decoration).max(min).max(1);
+    if max > 0 {
+        content = content.min(max.max(min));
+    }
+    content + decoration
+}
+
+/// Tells the client whether it is being resized interactively
+fn set_resizing(toplevel: &smithay::wayland::shell::xdg::ToplevelSurface, resizing: bool) {
+    toplevel.with_pending_state(|state| {
+        if resizing {
+            state.states.set(xdg_toplevel::State::Resizing);
+        } else {
+            state.states.unset(xdg_toplevel::State::Resizing);
+        }
+    });
+    toplevel.send_pending_configure();
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    /// Drags the window until the button of the grab is released
+    pub fn start_move(
+        &mut self,
+        window: desktop::Window,
+        start_data: GrabStartData<Self>,
+        serial: Serial,
+    ) {
+        let Some(pointer) = self.common.comp.seat.get_pointer() else {
+            return;
+        };
+
+        let initial_location = self
+            .common
+            .comp
+            .floating(&window)
+            .map(|floating| floating.geometry.loc);
+        pointer.set_grab(
+            self,
+            MoveGrab {
+                start_data,
+                window,
+                initial_location,
+            },
+            serial,
+            Focus::Clear,
+        );
+    }
+
+    /// Resizes the window until the button of the grab is released.
+    /// Tiled windows become floating first
+    pub fn start_resize(
+        &mut self,
+        window: desktop::Window,
+        edges: Edges,
+        start_data: GrabStartData<Self>,
+        serial: Serial,
+    ) {
+        let Some(pointer) = self.common.comp.seat.get_pointer() else {
+            return;
+        };
+
+        self.common.comp.float(&window);
+        let Some(initial_geometry) = self
+            .common
+            .comp
+            .floating(&window)
+            .map(|floating| floating.geometry)
+        else {
+            return;
+        };
+
+        let decoration = crate::shell::decoration(&window);
+        set_resize_anchor(
+            &window,
+            Some(ResizeAnchor {
+                edges,
+                bottom_right: initial_geometry.loc
+                    + Point::from((initial_geometry.size.w, initial_geometry.size.h)),
+                decoration,
+                finished: false,
+            }),
+        );
+
+        if let Some(toplevel) = window.toplevel() {
+            set_resizing(toplevel, true);
+        }
+
+        pointer.set_grab(
+            self,
+            ResizeGrab {
+                start_data,
+                window,
+                edges,
+                initial_geometry,
+                decoration,
+            },
+            serial,
+            Focus::Clear,
+        );
+    }
+}
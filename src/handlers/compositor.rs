@@ -1,6 +1,6 @@
 use smithay::{
     reexports::wayland_server::protocol::wl_surface::WlSurface,
-    wayland::compositor::CompositorHandler,
+    wayland::compositor::{with_states, CompositorHandler, SurfaceAttributes},
 };
 
 impl<B: crate::Backend> CompositorHandler for crate::App<B> {
@@ -12,6 +12,11 @@ impl<B: crate::Backend> CompositorHandler for crate::App<B> {
         &self,
         client: &'a smithay::reexports::wayland_server::Client,
     ) -> &'a smithay::wayland::compositor::CompositorClientState {
+        // Xwayland is spawned by smithay, which attaches its own client data
+        if let Some(data) = client.get_data::<smithay::xwayland::XWaylandClientData>() {
+            return &data.compositor_state;
+        }
+
         &client
             .get_data::<crate::state::ClientState>()
             .expect("Client has no ClientState")
@@ -20,7 +25,32 @@ impl<B: crate::Backend> CompositorHandler for crate::App<B> {
 
     fn commit(&mut self, surface: &WlSurface) {
         smithay::backend::renderer::utils::on_commit_buffer_handler::<crate::App<B>>(surface);
+        smithay::xwayland::X11Wm::commit_hook::<crate::App<B>>(surface);
 
         super::xdg_shell::handle_commit(self, surface);
+        super::layer_shell::handle_commit(self, surface);
+
+        // Drag-and-drop icons move their hotspot by attaching buffers with an offset
+        if let Some(icon) = self
+            .common
+            .comp
+            .dnd_icon
+            .as_mut()
+            .filter(|icon| &icon.surface == surface)
+        {
+            let delta = with_states(surface, |states| {
+                states
+                    .cached_state
+                    .get::<SurfaceAttributes>()
+                    .current()
+                    .buffer_delta
+                    .take()
+            });
+            if let Some(delta) = delta {
+                icon.offset += delta;
+            }
+        }
+
+        self.common.comp.schedule_redraw();
     }
 }
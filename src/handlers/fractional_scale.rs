@@ -0,0 +1,26 @@
+use smithay::{
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::{
+        compositor,
+        fractional_scale::{with_fractional_scale, FractionalScaleHandler},
+    },
+};
+
+impl<B: crate::Backend> FractionalScaleHandler for crate::App<B> {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        // Surfaces that are not shown yet most likely end up on the shell output
+        let comp = &self.common.comp;
+        let root = crate::util::surface::get_root_surface(&surface);
+        let output = crate::util::surface::find_window(&root, &comp.windows)
+            .and_then(|window| comp.space.outputs_for_element(&window).into_iter().next())
+            .or_else(|| comp.shell_output().cloned());
+
+        if let Some(output) = output {
+            compositor::with_states(&surface, |states| {
+                with_fractional_scale(states, |fractional_scale| {
+                    fractional_scale.set_preferred_scale(output.current_scale().fractional_scale());
+                });
+            });
+        }
+    }
+}
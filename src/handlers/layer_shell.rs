@@ -0,0 +1,143 @@
+use smithay::{
+    desktop::{self, layer_map_for_output, WindowSurfaceType},
+    output::Output,
+    reexports::wayland_server::protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    wayland::shell::wlr_layer::{
+        KeyboardInteractivity, Layer, LayerSurface, LayerSurfaceData, WlrLayerShellHandler,
+        WlrLayerShellState,
+    },
+};
+use tracing::error;
+
+impl<B: crate::Backend> WlrLayerShellHandler for crate::App<B> {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.common.comp.wl.layer_shell
+    }
+
+    fn new_layer_surface(
+        &mut self,
+        surface: LayerSurface,
+        output: Option<WlOutput>,
+        _layer: Layer,
+        namespace: String,
+    ) {
+        // Clients leave the choice of the output to the compositor by not specifying one
+        let Some(output) = output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(|| self.common.comp.shell_output().cloned())
+        else {
+            error!("Unable to place layer surface without any output");
+            surface.send_close();
+            return;
+        };
+
+        if let Err(err) =
+            layer_map_for_output(&output).map_layer(&desktop::LayerSurface::new(surface, namespace))
+        {
+            error!("Unable to map layer surface: {err}");
+        }
+    }
+
+    fn layer_destroyed(&mut self, surface: LayerSurface) {
+        for output in self.common.comp.space.outputs() {
+            let mut layer_map = layer_map_for_output(output);
+            if let Some(layer) = layer_map
+                .layers()
+                .find(|layer| layer.layer_surface() == &surface)
+                .cloned()
+            {
+                layer_map.unmap_layer(&layer);
+            }
+        }
+        self.common.comp.schedule_redraw();
+    }
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    /// Returns the layer surface under the given location and its location
+    pub fn layer_under(
+        &self,
+        layers: &[Layer],
+        location: smithay::utils::Point<f64, smithay::utils::Logical>,
+    ) -> Option<(
+        desktop::LayerSurface,
+        smithay::utils::Point<i32, smithay::utils::Logical>,
+    )> {
+        let space = &self.common.comp.space;
+        let output = space.output_under(location).next()?;
+        let output_location = space.output_geometry(output)?.loc;
+        let layer_map = layer_map_for_output(output);
+
+        layers.iter().find_map(|layer| {
+            let layer = layer_map.layer_under(*layer, location - output_location.to_f64())?;
+            let geometry = layer_map.layer_geometry(layer)?;
+            Some((layer.clone(), geometry.loc + output_location))
+        })
+    }
+
+    /// The surface of a top or overlay layer surface which demands all keyboard input
+    pub fn exclusive_layer(&self) -> Option<WlSurface> {
+        self.common.comp.space.outputs().find_map(|output| {
+            let layer_map = layer_map_for_output(output);
+            let exclusive = layer_map
+                .layers_on(Layer::Overlay)
+                .chain(layer_map.layers_on(Layer::Top))
+                .find(|layer| {
+                    layer.cached_state().keyboard_interactivity == KeyboardInteractivity::Exclusive
+                })
+                .map(|layer| layer.wl_surface().clone());
+            exclusive
+        })
+    }
+}
+
+/// Needs to be called on [CompositorHandler::commit] as a wl_surface can also be a layer surface
+pub fn handle_commit<B: crate::Backend>(app: &mut crate::App<B>, surface: &WlSurface) {
+    let Some(output) = app
+        .common
+        .comp
+        .space
+        .outputs()
+        .find(|output| {
+            layer_map_for_output(output)
+                .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                .is_some()
+        })
+        .cloned()
+    else {
+        return;
+    };
+
+    let layer = {
+        let mut layer_map = layer_map_for_output(&output);
+        // The committed state may change the exclusive zone and thereby the other layers
+        layer_map.arrange();
+        layer_map
+            .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+            .cloned()
+    };
+    let Some(layer) = layer else {
+        return;
+    };
+
+    let initial_configure_sent =
+        crate::util::surface::with_surface_data(surface, |mutex: &LayerSurfaceData| {
+            mutex.lock().unwrap().initial_configure_sent
+        });
+    if let Some(false) = initial_configure_sent {
+        layer.layer_surface().send_configure();
+    }
+
+    let focus = app
+        .common
+        .comp
+        .seat
+        .get_keyboard()
+        .and_then(|keyboard| keyboard.current_focus());
+    if let Some(exclusive) = app.exclusive_layer() {
+        if focus.as_ref() != Some(&exclusive) {
+            app.set_focus(exclusive);
+        }
+    }
+}
@@ -2,10 +2,15 @@ use smithay::*;
 
 mod compositor;
 mod dmabuf;
+mod fractional_scale;
+mod layer_shell;
 mod output;
 mod seat;
+mod selection;
 mod shm;
+mod xdg_decoration;
 mod xdg_shell;
+mod xwayland;
 
 /// Run `delegate_xxxx!` for each argument disregarding the backend type
 macro_rules! delegate_for_all_backends {
@@ -20,9 +25,17 @@ macro_rules! delegate_for_all_backends {
 }
 delegate_for_all_backends!(
     compositor
+    data_device
     dmabuf
+    fractional_scale
+    layer_shell
     output
+    presentation
+    primary_selection
     seat
     shm
+    viewporter
+    xdg_decoration
     xdg_shell
+    xwayland_shell
 );
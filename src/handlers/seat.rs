@@ -1,5 +1,6 @@
 use smithay::input;
-use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource};
+use tracing::warn;
 
 impl<B: crate::Backend> input::SeatHandler for crate::App<B> {
     type KeyboardFocus = WlSurface;
@@ -9,4 +10,53 @@ impl<B: crate::Backend> input::SeatHandler for crate::App<B> {
     fn seat_state(&mut self) -> &mut input::SeatState<Self> {
         &mut self.common.comp.wl.seat
     }
+
+    fn focus_changed(&mut self, seat: &input::Seat<Self>, focused: Option<&WlSurface>) {
+        // Only the client with keyboard focus may access the selection
+        let display_handle = &self.common.comp.display_handle;
+        let client = focused.and_then(|surface| display_handle.get_client(surface.id()).ok());
+        smithay::wayland::selection::data_device::set_data_device_focus(
+            display_handle,
+            seat,
+            client.clone(),
+        );
+        smithay::wayland::selection::primary_selection::set_primary_focus(
+            display_handle,
+            seat,
+            client,
+        );
+
+        let window = focused.and_then(|surface| {
+            crate::util::surface::find_window(
+                &crate::util::surface::get_root_surface(surface),
+                &self.common.comp.windows,
+            )
+        });
+
+        // X11 clients only learn about the focus through the window manager
+        for other in &self.common.comp.windows {
+            let Some(x11) = other.x11_surface() else {
+                continue;
+            };
+            let activated = window.as_ref() == Some(other);
+            if x11.is_activated() != activated {
+                if let Err(err) = x11.set_activated(activated) {
+                    warn!("Unable to activate X11 window: {err}");
+                }
+            }
+        }
+        if let (Some(xwm), Some(x11)) = (
+            &mut self.common.comp.xwm,
+            window.as_ref().and_then(|window| window.x11_surface()),
+        ) {
+            if let Err(err) = xwm.raise_window(x11) {
+                warn!("Unable to raise X11 window: {err}");
+            }
+        }
+
+        let window = window.map(|window| self.window_info(&window, true));
+        self.common.comp.ipc.focus_changed(window);
+        // Title bars show which window is focused
+        self.common.comp.schedule_redraw();
+    }
 }
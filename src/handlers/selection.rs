@@ -0,0 +1,331 @@
+use iced_core::clipboard::Kind;
+use smithay::{
+    input::Seat,
+    reexports::{
+        calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction, RegistrationToken},
+        rustix,
+        wayland_server::protocol::{wl_data_source::WlDataSource, wl_surface::WlSurface},
+    },
+    wayland::selection::{
+        data_device::{
+            self, ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler,
+        },
+        primary_selection::{self, PrimarySelectionHandler, PrimarySelectionState},
+        SelectionHandler, SelectionSource, SelectionTarget,
+    },
+};
+use std::{
+    io::{ErrorKind, Read, Write},
+    os::{fd::OwnedFd, unix::net::UnixStream},
+};
+use tracing::{error, warn};
+
+/// Mime types for plain text, most preferred first
+const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+    "TEXT",
+    "STRING",
+];
+
+/// Longest client selection handed to the shell. Longer ones cannot be pasted in the shell
+const MAX_SELECTION_LEN: usize = 1024 * 1024;
+
+/// Selection owned by the compositor instead of a Wayland client
+pub enum SelectionUserData {
+    /// Text copied in the shell
+    Text(String),
+    /// Selection of an X11 client, served by the X11 window manager
+    Xwayland,
+}
+
+/// Returns the preferred text mime type out of the offered ones
+pub fn text_mime_type(offered: &[String]) -> Option<&'static str> {
+    TEXT_MIME_TYPES
+        .into_iter()
+        .find(|mime_type| offered.iter().any(|offered| offered == mime_type))
+}
+
+/// Sources reading client selections for the shell, one per target
+#[derive(Default)]
+pub struct SelectionReaders {
+    clipboard: Option<RegistrationToken>,
+    primary: Option<RegistrationToken>,
+}
+
+impl SelectionReaders {
+    fn get_mut(&mut self, target: SelectionTarget) -> &mut Option<RegistrationToken> {
+        match target {
+            SelectionTarget::Clipboard => &mut self.clipboard,
+            SelectionTarget::Primary => &mut self.primary,
+        }
+    }
+}
+
+pub fn clipboard_kind(target: SelectionTarget) -> Kind {
+    match target {
+        SelectionTarget::Clipboard => Kind::Standard,
+        SelectionTarget::Primary => Kind::Primary,
+    }
+}
+
+impl<B: crate::Backend> SelectionHandler for crate::App<B> {
+    type SelectionUserData = SelectionUserData;
+
+    fn new_selection(
+        &mut self,
+        target: SelectionTarget,
+        source: Option<SelectionSource>,
+        seat: Seat<Self>,
+    ) {
+        let offered = source.map(|source| source.mime_types());
+        self.common.comp.cancel_selection_read(target);
+
+        // X11 clients get the selection of Wayland clients through the window manager
+        if let Some(xwm) = &mut self.common.comp.xwm {
+            if let Err(err) = xwm.new_selection(target, offered.clone()) {
+                warn!("Unable to forward selection to Xwayland: {err}");
+            }
+        }
+
+        match offered.as_deref().and_then(text_mime_type) {
+            Some(mime_type) => self.read_client_selection(target, mime_type.to_string(), &seat),
+            None => self
+                .common
+                .shell_driver
+                .set_clipboard(clipboard_kind(target), None),
+        }
+    }
+
+    fn send_selection(
+        &mut self,
+        target: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+        _seat: Seat<Self>,
+        user_data: &Self::SelectionUserData,
+    ) {
+        match user_data {
+            SelectionUserData::Text(text) => {
+                write_selection(&self.common.comp.loop_handle, fd, text.clone())
+            }
+            SelectionUserData::Xwayland => {
+                let loop_handle = self.common.comp.loop_handle.clone();
+                let Some(xwm) = &mut self.common.comp.xwm else {
+                    return;
+                };
+                if let Err(err) = xwm.send_selection(target, mime_type, fd, loop_handle) {
+                    error!("Unable to request X11 selection: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Writes the text to the client reading the selection whenever it can take more.
+/// The client may only read once the event loop dispatched its request,
+/// so writing must not block the event loop
+pub fn write_selection<B: crate::Backend>(
+    loop_handle: &LoopHandle<'static, crate::App<B>>,
+    fd: OwnedFd,
+    text: String,
+) {
+    let nonblocking = rustix::fs::fcntl_getfl(&fd)
+        .and_then(|flags| rustix::fs::fcntl_setfl(&fd, flags | rustix::fs::OFlags::NONBLOCK));
+    if let Err(err) = nonblocking {
+        error!("Unable to make selection fd non-blocking: {err}");
+        return;
+    }
+
+    let mut written = 0;
+    let source = Generic::new(std::fs::File::from(fd), Interest::WRITE, Mode::Level);
+
+    if let Err(err) = loop_handle.insert_source(source, move |_, writer, _| {
+        let text = text.as_bytes();
+        while written < text.len() {
+            match writer.write(&text[written..]) {
+                Ok(0) => break,
+                Ok(count) => written += count,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                Err(err) => {
+                    error!("Unable to send selection: {err}");
+                    break;
+                }
+            }
+        }
+        Ok(PostAction::Remove)
+    }) {
+        error!("Unable to insert selection writer: {err}");
+    }
+}
+
+impl<B: crate::Backend> DataDeviceHandler for crate::App<B> {
+    fn data_device_state(&self) -> &DataDeviceState {
+        &self.common.comp.wl.data_device
+    }
+}
+
+impl<B: crate::Backend> ClientDndGrabHandler for crate::App<B> {
+    fn started(
+        &mut self,
+        _source: Option<WlDataSource>,
+        icon: Option<WlSurface>,
+        _seat: Seat<Self>,
+    ) {
+        self.common.comp.dnd_icon = icon.map(|surface| crate::state::DndIcon {
+            surface,
+            offset: (0, 0).into(),
+        });
+    }
+
+    fn dropped(&mut self, _target: Option<WlSurface>, _validated: bool, _seat: Seat<Self>) {
+        self.common.comp.dnd_icon = None;
+    }
+}
+
+impl<B: crate::Backend> ServerDndGrabHandler for crate::App<B> {}
+
+impl<B: crate::Backend> PrimarySelectionHandler for crate::App<B> {
+    fn primary_selection_state(&self) -> &PrimarySelectionState {
+        &self.common.comp.wl.primary_selection
+    }
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    /// Reads the text of a client selection without blocking and hands it to the shell
+    fn read_client_selection(
+        &mut self,
+        target: SelectionTarget,
+        mime_type: String,
+        seat: &Seat<Self>,
+    ) {
+        let (reader, writer) = match UnixStream::pair() {
+            Ok(pair) => pair,
+            Err(err) => {
+                error!("Unable to create selection socket: {err}");
+                return;
+            }
+        };
+
+        let requested = match target {
+            SelectionTarget::Clipboard => {
+                data_device::request_data_device_client_selection(seat, mime_type, writer.into())
+            }
+            SelectionTarget::Primary => {
+                primary_selection::request_primary_client_selection(seat, mime_type, writer.into())
+            }
+        };
+        if let Err(err) = requested {
+            error!("Unable to request client selection: {err}");
+            return;
+        }
+
+        self.read_selection(target, reader);
+    }
+
+    /// Reads the text of an X11 selection without blocking and hands it to the shell
+    pub fn read_x11_selection(&mut self, target: SelectionTarget, mime_type: String) {
+        let (reader, writer) = match UnixStream::pair() {
+            Ok(pair) => pair,
+            Err(err) => {
+                error!("Unable to create selection socket: {err}");
+                return;
+            }
+        };
+
+        let loop_handle = self.common.comp.loop_handle.clone();
+        let Some(xwm) = &mut self.common.comp.xwm else {
+            return;
+        };
+        if let Err(err) = xwm.send_selection(target, mime_type, writer.into(), loop_handle) {
+            error!("Unable to request X11 selection: {err}");
+            return;
+        }
+
+        self.read_selection(target, reader);
+    }
+
+    /// Reads the selection written into the socket and hands it to the shell once complete.
+    /// The read is cancelled if the selection changes before it completes
+    fn read_selection(&mut self, target: SelectionTarget, reader: UnixStream) {
+        if let Err(err) = reader.set_nonblocking(true) {
+            error!("Unable to make selection socket non-blocking: {err}");
+            return;
+        }
+
+        let kind = clipboard_kind(target);
+        let mut contents = Vec::new();
+        let source = Generic::new(reader, Interest::READ, Mode::Level);
+
+        let inserted = self
+            .common
+            .comp
+            .loop_handle
+            .insert_source(source, move |_, reader, app| {
+                let mut chunk = [0; 4096];
+                let text = loop {
+                    match reader.read(&mut chunk) {
+                        Ok(0) => break Some(String::from_utf8_lossy(&contents).into_owned()),
+                        Ok(read) if contents.len() + read > MAX_SELECTION_LEN => {
+                            warn!("Selection is too long to be pasted in the shell");
+                            break None;
+                        }
+                        Ok(read) => contents.extend_from_slice(&chunk[..read]),
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                            return Ok(PostAction::Continue)
+                        }
+                        Err(err) => {
+                            error!("Unable to read selection: {err}");
+                            break None;
+                        }
+                    }
+                };
+
+                app.common.shell_driver.set_clipboard(kind, text);
+                *app.common.comp.selection_readers.get_mut(target) = None;
+                Ok(PostAction::Remove)
+            });
+        match inserted {
+            Ok(token) => *self.common.comp.selection_readers.get_mut(target) = Some(token),
+            Err(err) => error!("Unable to insert selection source: {err}"),
+        }
+    }
+}
+
+impl<B: crate::Backend> crate::state::Compositor<B> {
+    /// Makes the compositor own the selection, offering the text to clients
+    pub fn set_text_selection(&mut self, target: SelectionTarget, text: String) {
+        self.cancel_selection_read(target);
+        let mime_types = TEXT_MIME_TYPES.map(String::from).to_vec();
+
+        if let Some(xwm) = &mut self.xwm {
+            if let Err(err) = xwm.new_selection(target, Some(mime_types.clone())) {
+                warn!("Unable to forward selection to Xwayland: {err}");
+            }
+        }
+
+        let text = SelectionUserData::Text(text);
+        match target {
+            SelectionTarget::Clipboard => data_device::set_data_device_selection(
+                &self.display_handle,
+                &self.seat,
+                mime_types,
+                text,
+            ),
+            SelectionTarget::Primary => primary_selection::set_primary_selection(
+                &self.display_handle,
+                &self.seat,
+                mime_types,
+                text,
+            ),
+        }
+    }
+
+    /// Stops reading the previous selection, which would replace the newer one once complete
+    pub fn cancel_selection_read(&mut self, target: SelectionTarget) {
+        if let Some(token) = self.selection_readers.get_mut(target).take() {
+            self.loop_handle.remove(token);
+        }
+    }
+}
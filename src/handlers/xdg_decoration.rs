@@ -0,0 +1,27 @@
+use smithay::{
+    reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode,
+    wayland::shell::xdg::{decoration::XdgDecorationHandler, ToplevelSurface},
+};
+
+/// The shell draws the title bars, so clients are always asked to leave out their own
+fn use_server_side(toplevel: &ToplevelSurface) {
+    toplevel.with_pending_state(|state| state.decoration_mode = Some(Mode::ServerSide));
+
+    if toplevel.is_initial_configure_sent() {
+        toplevel.send_pending_configure();
+    }
+}
+
+impl<B: crate::Backend> XdgDecorationHandler for crate::App<B> {
+    fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        use_server_side(&toplevel);
+    }
+
+    fn request_mode(&mut self, toplevel: ToplevelSurface, _mode: Mode) {
+        use_server_side(&toplevel);
+    }
+
+    fn unset_mode(&mut self, toplevel: ToplevelSurface) {
+        use_server_side(&toplevel);
+    }
+}
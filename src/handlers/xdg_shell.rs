@@ -2,8 +2,19 @@ use std::ops::Not;
 
 use smithay::{
     desktop,
-    reexports::wayland_server::protocol::wl_surface::WlSurface,
-    wayland::shell::xdg::{XdgShellHandler, XdgToplevelSurfaceData},
+    input::{
+        pointer::{GrabStartData, PointerHandle},
+        Seat,
+    },
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        wayland_server::{
+            protocol::{wl_output::WlOutput, wl_seat::WlSeat, wl_surface::WlSurface},
+            Resource as _,
+        },
+    },
+    utils::Serial,
+    wayland::shell::xdg::{ToplevelSurface, XdgShellHandler, XdgToplevelSurfaceData},
 };
 use tracing::error;
 
@@ -15,34 +26,264 @@ impl<B: crate::Backend> XdgShellHandler for crate::App<B> {
     fn new_toplevel(&mut self, surface: smithay::wayland::shell::xdg::ToplevelSurface) {
         let wl_surface = surface.wl_surface().clone();
         let window = desktop::Window::new_wayland_window(surface);
-        self.common.comp.space.map_element(window, (0, 0), true);
+        // The shell layout moves the window to its actual location
+        self.common.comp.windows.push(window.clone());
+        self.common
+            .comp
+            .workspace_mut()
+            .windows
+            .push(window.clone());
+        self.common
+            .comp
+            .space
+            .map_element(window.clone(), (0, 0), true);
         self.set_focus(wl_surface);
     }
 
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self
+            .toplevel_window(&surface)
+            .filter(|window| window.user_data().get::<Announced>().is_some())
+        {
+            let id = crate::ipc::window_id(&window);
+            self.common
+                .comp
+                .ipc
+                .emit(crate::ipc::protocol::Event::WindowClosed { id });
+        }
+        self.common.comp.schedule_redraw();
+    }
+
+    fn popup_destroyed(&mut self, _surface: smithay::wayland::shell::xdg::PopupSurface) {
+        self.common.comp.schedule_redraw();
+    }
+
     fn new_popup(
         &mut self,
-        _surfacee: smithay::wayland::shell::xdg::PopupSurface,
-        _positioner: smithay::wayland::shell::xdg::PositionerState,
+        surface: smithay::wayland::shell::xdg::PopupSurface,
+        positioner: smithay::wayland::shell::xdg::PositionerState,
     ) {
-        todo!()
+        surface.with_pending_state(|state| state.geometry = positioner.get_geometry());
+        self.unconstrain_popup(&surface);
+
+        if let Err(err) = self
+            .common
+            .comp
+            .popups
+            .track_popup(desktop::PopupKind::Xdg(surface))
+        {
+            error!("Unable to track popup: {err}");
+        }
     }
 
     fn grab(
         &mut self,
-        _surface: smithay::wayland::shell::xdg::PopupSurface,
+        surface: smithay::wayland::shell::xdg::PopupSurface,
         _seat: smithay::reexports::wayland_server::protocol::wl_seat::WlSeat,
-        _serial: smithay::utils::Serial,
+        serial: smithay::utils::Serial,
     ) {
-        todo!()
+        let kind = desktop::PopupKind::Xdg(surface);
+        let Ok(root) = desktop::find_popup_root_surface(&kind) else {
+            return;
+        };
+
+        let seat = self.common.comp.seat.clone();
+        let Ok(mut grab) = self
+            .common
+            .comp
+            .popups
+            .grab_popup::<crate::App<B>>(root, kind, &seat, serial)
+        else {
+            return;
+        };
+
+        if let Some(keyboard) = seat.get_keyboard() {
+            // Only allow the grab if it replaces an existing grab of the same popup chain
+            if keyboard.is_grabbed()
+                && !(keyboard.has_grab(serial)
+                    || keyboard.has_grab(grab.previous_serial().unwrap_or(serial)))
+            {
+                grab.ungrab(desktop::PopupUngrabStrategy::All);
+                return;
+            }
+            keyboard.set_focus(self, grab.current_grab(), serial);
+            keyboard.set_grab(self, desktop::PopupKeyboardGrab::new(&grab), serial);
+        }
+
+        if let Some(pointer) = seat.get_pointer() {
+            if pointer.is_grabbed()
+                && !(pointer.has_grab(serial)
+                    || pointer.has_grab(grab.previous_serial().unwrap_or_else(|| grab.serial())))
+            {
+                grab.ungrab(desktop::PopupUngrabStrategy::All);
+                return;
+            }
+            pointer.set_grab(
+                self,
+                desktop::PopupPointerGrab::new(&grab),
+                serial,
+                smithay::input::pointer::Focus::Keep,
+            );
+        }
+    }
+
+    fn move_request(&mut self, surface: ToplevelSurface, seat: WlSeat, serial: Serial) {
+        if let Some((window, start_data)) = self.toplevel_grab_start(&surface, &seat, serial) {
+            self.start_move(window, start_data, serial);
+        }
+    }
+
+    fn resize_request(
+        &mut self,
+        surface: ToplevelSurface,
+        seat: WlSeat,
+        serial: Serial,
+        edges: xdg_toplevel::ResizeEdge,
+    ) {
+        if let Some((window, start_data)) = self.toplevel_grab_start(&surface, &seat, serial) {
+            self.start_resize(window, edges.into(), start_data, serial);
+        }
+    }
+
+    fn fullscreen_request(&mut self, surface: ToplevelSurface, _output: Option<WlOutput>) {
+        // The shell only lives on a single output, so that is where the window goes
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.request_fullscreen(&window, true);
+        }
+    }
+
+    fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.request_fullscreen(&window, false);
+        }
+    }
+
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.request_maximized(&window, true);
+        }
+    }
+
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.request_maximized(&window, false);
+        }
+    }
+
+    fn minimize_request(&mut self, surface: ToplevelSurface) {
+        self.run_action(crate::action::Action::Minimize(
+            crate::action::Target::Window(surface.wl_surface().clone()),
+        ));
     }
 
     fn reposition_request(
         &mut self,
-        _surface: smithay::wayland::shell::xdg::PopupSurface,
-        _positioner: smithay::wayland::shell::xdg::PositionerState,
-        _token: u32,
+        surface: smithay::wayland::shell::xdg::PopupSurface,
+        positioner: smithay::wayland::shell::xdg::PositionerState,
+        token: u32,
     ) {
-        todo!()
+        surface.with_pending_state(|state| {
+            state.geometry = positioner.get_geometry();
+            state.positioner = positioner;
+        });
+        self.unconstrain_popup(&surface);
+        surface.send_repositioned(token);
+    }
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    fn toplevel_window(&self, toplevel: &ToplevelSurface) -> Option<desktop::Window> {
+        crate::util::surface::find_window(toplevel.wl_surface(), &self.common.comp.windows)
+    }
+
+    /// Returns the window of the toplevel and the start data of the pointer grab it requested
+    fn toplevel_grab_start(
+        &self,
+        toplevel: &ToplevelSurface,
+        seat: &WlSeat,
+        serial: Serial,
+    ) -> Option<(desktop::Window, GrabStartData<Self>)> {
+        let pointer = Seat::<Self>::from_resource(seat)?.get_pointer()?;
+        if !pointer.has_grab(serial) {
+            return None;
+        }
+
+        let window = self.toplevel_window(toplevel)?;
+        let start_data = self.client_grab_start(&pointer, &window)?;
+        Some((window, start_data))
+    }
+
+    /// Returns the start data of the pointer grab the client of the window requested.
+    /// Grabs are only allowed while the client has a button pressed on one of its surfaces
+    pub(super) fn client_grab_start(
+        &self,
+        pointer: &PointerHandle<Self>,
+        window: &desktop::Window,
+    ) -> Option<GrabStartData<Self>> {
+        let start_data = pointer.grab_start_data()?;
+        let (focus, _) = start_data.focus.as_ref()?;
+        if !focus.id().same_client_as(&window.wl_surface()?.id()) {
+            return None;
+        }
+
+        Some(start_data)
+    }
+
+    /// Applies a fullscreen request of the client of the window
+    pub(super) fn request_fullscreen(&mut self, window: &desktop::Window, fullscreen: bool) {
+        if let Some(workspace) = self.common.comp.workspaces.containing_mut(window) {
+            workspace.set_fullscreen(window, fullscreen);
+        }
+        self.focus_requested(window, fullscreen);
+    }
+
+    /// Applies a maximize request of the client of the window
+    pub(super) fn request_maximized(&mut self, window: &desktop::Window, maximized: bool) {
+        if let Some(workspace) = self.common.comp.workspaces.containing_mut(window) {
+            workspace.set_maximized(window, maximized);
+        }
+        self.focus_requested(window, maximized);
+    }
+
+    /// Focuses a window that was fullscreened or maximized on request of its client
+    fn focus_requested(&mut self, window: &desktop::Window, enlarged: bool) {
+        // Windows on hidden workspaces must not take the focus from the shown ones
+        if enlarged && self.common.comp.is_on_shown_workspace(window) {
+            self.focus_window(window);
+        }
+    }
+
+    /// Moves the popup so it stays inside the output of its root window
+    fn unconstrain_popup(&self, popup: &smithay::wayland::shell::xdg::PopupSurface) {
+        let kind = desktop::PopupKind::Xdg(popup.clone());
+        let Ok(root) = desktop::find_popup_root_surface(&kind) else {
+            return;
+        };
+
+        let space = &self.common.comp.space;
+        let Some(window) = crate::util::surface::find_window(&root, &self.common.comp.windows)
+        else {
+            return;
+        };
+        let Some(window_location) = space.element_location(&window) else {
+            return;
+        };
+        let Some(mut target) = space
+            .outputs_for_element(&window)
+            .first()
+            .or(space.outputs().next())
+            .and_then(|output| space.output_geometry(output))
+        else {
+            return;
+        };
+
+        // The positioner works relative to the parent surface
+        target.loc -= window_location;
+        target.loc -= desktop::get_popup_toplevel_coords(&kind);
+
+        popup.with_pending_state(|state| {
+            state.geometry = state.positioner.get_unconstrained_geometry(target);
+        });
     }
 }
 
@@ -54,13 +295,24 @@ pub fn handle_commit<B: crate::Backend>(app: &mut crate::App<B>, surface: &WlSur
         let root_surface = crate::util::surface::get_root_surface(surface);
 
         if let Some(window) =
-            crate::util::surface::find_window(&root_surface, &app.common.comp.space)
+            crate::util::surface::find_window(&root_surface, &app.common.comp.windows)
         {
             window.on_commit();
         }
     }
 
-    if let Some(window) = crate::util::surface::find_window(surface, &app.common.comp.space) {
+    app.common.comp.popups.commit(surface);
+
+    if let Some(desktop::PopupKind::Xdg(popup)) = app.common.comp.popups.find_popup(surface) {
+        if !popup.is_initial_configure_sent() {
+            // Popups also need their initial configure on the initial commit
+            if let Err(err) = popup.send_configure() {
+                error!("Unable to send initial popup configure: {err}");
+            }
+        }
+    }
+
+    if let Some(window) = crate::util::surface::find_window(surface, &app.common.comp.windows) {
         if let Some(top_level) = window.toplevel() {
             let initial_configure_pending: Option<bool> = crate::util::surface::with_surface_data(
                 surface,
@@ -74,6 +326,30 @@ pub fn handle_commit<B: crate::Backend>(app: &mut crate::App<B>, surface: &WlSur
             } else if initial_configure_pending.is_none() {
                 error!("Unable to obtain XdgToplevelSurfaceData from top-level surface");
             }
+
+            let mapped =
+                smithay::backend::renderer::utils::with_renderer_surface_state(surface, |state| {
+                    state.buffer().is_some()
+                })
+                .unwrap_or(false);
+            // The title and app id are only known once the toplevel is mapped
+            if mapped
+                && window
+                    .user_data()
+                    .insert_if_missing_threadsafe(|| Announced)
+            {
+                let focused = app.focused_window().as_ref() == Some(&window);
+                let window = app.window_info(&window, focused);
+                app.common
+                    .comp
+                    .ipc
+                    .emit(crate::ipc::protocol::Event::WindowOpened { window });
+            }
         }
+
+        crate::grabs::handle_resize_commit(&mut app.common.comp, &window);
     }
 }
+
+/// Marks toplevels whose opening was announced over IPC, stored in the user data of the window
+struct Announced;
@@ -0,0 +1,375 @@
+use smithay::{
+    desktop,
+    input::pointer::GrabStartData,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Rectangle, SERIAL_COUNTER},
+    wayland::{
+        selection::{data_device, primary_selection, SelectionTarget},
+        xwayland_shell::{XWaylandShellHandler, XWaylandShellState},
+    },
+    xwayland::{
+        xwm::{Reorder, ResizeEdge, XwmId},
+        X11Surface, X11Wm, XWayland, XWaylandEvent, XwmHandler,
+    },
+};
+use std::{os::fd::OwnedFd, process::Stdio};
+use tracing::{error, info, warn};
+
+use super::selection::SelectionUserData;
+
+impl<B: crate::Backend> XWaylandShellHandler for crate::App<B> {
+    fn xwayland_shell_state(&mut self) -> &mut XWaylandShellState {
+        &mut self.common.comp.wl.xwayland_shell
+    }
+
+    fn surface_associated(&mut self, _xwm: XwmId, _surface: WlSurface, window: X11Surface) {
+        // The window could not take the focus when it was mapped without a surface
+        if let Some(window) = self.x11_window(&window) {
+            self.focus_window(&window);
+        }
+    }
+}
+
+impl<B: crate::Backend> XwmHandler for crate::App<B> {
+    fn xwm_state(&mut self, _xwm: XwmId) -> &mut X11Wm {
+        self.common
+            .comp
+            .xwm
+            .as_mut()
+            .expect("Xwayland is not running")
+    }
+
+    fn new_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn new_override_redirect_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn map_window_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Err(err) = surface.set_mapped(true) {
+            error!("Unable to map X11 window: {err}");
+            return;
+        }
+
+        let window = desktop::Window::new_x11_window(surface);
+        // The shell layout moves the window to its actual location
+        self.common.comp.windows.push(window.clone());
+        self.common
+            .comp
+            .workspace_mut()
+            .windows
+            .push(window.clone());
+        self.common
+            .comp
+            .space
+            .map_element(window.clone(), (0, 0), true);
+
+        let info = self.window_info(&window, false);
+        self.common
+            .comp
+            .ipc
+            .emit(crate::ipc::protocol::Event::WindowOpened { window: info });
+        self.focus_window(&window);
+    }
+
+    fn mapped_override_redirect_window(&mut self, _xwm: XwmId, window: X11Surface) {
+        self.common.comp.override_redirect.push(window);
+    }
+
+    fn unmapped_window(&mut self, _xwm: XwmId, window: X11Surface) {
+        self.remove_x11_window(&window);
+        if !window.is_override_redirect() {
+            if let Err(err) = window.set_mapped(false) {
+                warn!("Unable to unmap X11 window: {err}");
+            }
+        }
+    }
+
+    fn destroyed_window(&mut self, _xwm: XwmId, window: X11Surface) {
+        self.remove_x11_window(&window);
+    }
+
+    fn configure_request(
+        &mut self,
+        _xwm: XwmId,
+        window: X11Surface,
+        x: Option<i32>,
+        y: Option<i32>,
+        w: Option<u32>,
+        h: Option<u32>,
+        _reorder: Option<Reorder>,
+    ) {
+        // Managed windows are placed by the shell, so they are told their current geometry again
+        let result = if self.x11_window(&window).is_some() {
+            window.configure(None)
+        } else {
+            let mut geometry = window.geometry();
+            geometry.loc.x = x.unwrap_or(geometry.loc.x);
+            geometry.loc.y = y.unwrap_or(geometry.loc.y);
+            geometry.size.w = w.map_or(geometry.size.w, |w| w as i32);
+            geometry.size.h = h.map_or(geometry.size.h, |h| h as i32);
+            window.configure(geometry)
+        };
+
+        if let Err(err) = result {
+            warn!("Unable to configure X11 window: {err}");
+        }
+    }
+
+    fn configure_notify(
+        &mut self,
+        _xwm: XwmId,
+        _window: X11Surface,
+        _geometry: Rectangle<i32, Logical>,
+        _above: Option<u32>,
+    ) {
+    }
+
+    fn resize_request(
+        &mut self,
+        _xwm: XwmId,
+        window: X11Surface,
+        _button: u32,
+        resize_edge: ResizeEdge,
+    ) {
+        if let Some((window, start_data)) = self.x11_grab_start(&window) {
+            self.start_resize(
+                window,
+                resize_edge.into(),
+                start_data,
+                SERIAL_COUNTER.next_serial(),
+            );
+        }
+    }
+
+    fn move_request(&mut self, _xwm: XwmId, window: X11Surface, _button: u32) {
+        if let Some((window, start_data)) = self.x11_grab_start(&window) {
+            self.start_move(window, start_data, SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    fn fullscreen_request(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Some(window) = self.x11_window(&window) {
+            self.request_fullscreen(&window, true);
+        }
+    }
+
+    fn unfullscreen_request(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Some(window) = self.x11_window(&window) {
+            self.request_fullscreen(&window, false);
+        }
+    }
+
+    fn maximize_request(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Some(window) = self.x11_window(&window) {
+            self.request_maximized(&window, true);
+        }
+    }
+
+    fn unmaximize_request(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Some(window) = self.x11_window(&window) {
+            self.request_maximized(&window, false);
+        }
+    }
+
+    fn minimize_request(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Some(surface) = window.wl_surface() {
+            self.run_action(crate::action::Action::Minimize(
+                crate::action::Target::Window(surface),
+            ));
+        }
+    }
+
+    fn allow_selection_access(&mut self, xwm: XwmId, _selection: SelectionTarget) -> bool {
+        // Like Wayland clients, X11 clients only get the selection while they have the focus
+        self.focused_window()
+            .and_then(|window| window.x11_surface().and_then(X11Surface::xwm_id))
+            == Some(xwm)
+    }
+
+    fn send_selection(
+        &mut self,
+        _xwm: XwmId,
+        selection: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+    ) {
+        let seat = &self.common.comp.seat;
+
+        // The selection of the shell is not owned by any client
+        let text = match selection {
+            SelectionTarget::Clipboard => data_device::current_data_device_selection_userdata(seat)
+                .and_then(|data| match &*data {
+                    SelectionUserData::Text(text) => Some(text.clone()),
+                    SelectionUserData::Xwayland => None,
+                }),
+            SelectionTarget::Primary => primary_selection::current_primary_selection_userdata(seat)
+                .and_then(|data| match &*data {
+                    SelectionUserData::Text(text) => Some(text.clone()),
+                    SelectionUserData::Xwayland => None,
+                }),
+        };
+        if let Some(text) = text {
+            super::selection::write_selection(&self.common.comp.loop_handle, fd, text);
+            return;
+        }
+
+        let requested = match selection {
+            SelectionTarget::Clipboard => {
+                data_device::request_data_device_client_selection(seat, mime_type, fd)
+            }
+            SelectionTarget::Primary => {
+                primary_selection::request_primary_client_selection(seat, mime_type, fd)
+            }
+        };
+        if let Err(err) = requested {
+            error!("Unable to request client selection for Xwayland: {err}");
+        }
+    }
+
+    fn new_selection(&mut self, _xwm: XwmId, selection: SelectionTarget, mime_types: Vec<String>) {
+        let text_mime_type = super::selection::text_mime_type(&mime_types);
+        self.common.comp.cancel_selection_read(selection);
+
+        let comp = &self.common.comp;
+        match selection {
+            SelectionTarget::Clipboard => data_device::set_data_device_selection(
+                &comp.display_handle,
+                &comp.seat,
+                mime_types,
+                SelectionUserData::Xwayland,
+            ),
+            SelectionTarget::Primary => primary_selection::set_primary_selection(
+                &comp.display_handle,
+                &comp.seat,
+                mime_types,
+                SelectionUserData::Xwayland,
+            ),
+        }
+
+        let kind = super::selection::clipboard_kind(selection);
+        match text_mime_type {
+            Some(mime_type) => self.read_x11_selection(selection, mime_type.to_string()),
+            None => self.common.shell_driver.set_clipboard(kind, None),
+        }
+    }
+
+    fn cleared_selection(&mut self, _xwm: XwmId, selection: SelectionTarget) {
+        let comp = &self.common.comp;
+        let owned_by_x11 =
+            |data: Option<&SelectionUserData>| matches!(data, Some(SelectionUserData::Xwayland));
+
+        // The selection may already belong to someone else
+        match selection {
+            SelectionTarget::Clipboard => {
+                if owned_by_x11(
+                    data_device::current_data_device_selection_userdata(&comp.seat).as_deref(),
+                ) {
+                    data_device::clear_data_device_selection(&comp.display_handle, &comp.seat);
+                }
+            }
+            SelectionTarget::Primary => {
+                if owned_by_x11(
+                    primary_selection::current_primary_selection_userdata(&comp.seat).as_deref(),
+                ) {
+                    primary_selection::clear_primary_selection(&comp.display_handle, &comp.seat);
+                }
+            }
+        }
+    }
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    /// Spawns Xwayland once the event loop runs, not on the first X11 connection:
+    /// `XWayland::spawn` binds the X11 sockets and starts the server in one step.
+    /// X11 clients can connect as soon as `DISPLAY` is set
+    pub fn start_xwayland(&mut self) {
+        self.common.comp.loop_handle.insert_idle(|app| {
+            let comp = &app.common.comp;
+            let (xwayland, client) = match XWayland::spawn(
+                &comp.display_handle,
+                None,
+                std::iter::empty::<(String, String)>(),
+                true,
+                Stdio::null(),
+                Stdio::null(),
+                |_| (),
+            ) {
+                Ok(spawned) => spawned,
+                Err(err) => {
+                    error!("Unable to spawn Xwayland: {err}");
+                    return;
+                }
+            };
+
+            let result =
+                comp.loop_handle
+                    .insert_source(xwayland, move |event, _, app| match event {
+                        XWaylandEvent::Ready {
+                            x11_socket,
+                            display_number,
+                        } => {
+                            let loop_handle = app.common.comp.loop_handle.clone();
+                            match X11Wm::start_wm(loop_handle, x11_socket, client.clone()) {
+                                Ok(xwm) => {
+                                    info!("Xwayland is ready on display :{display_number}");
+                                    // Processes spawned by the compositor should use this server
+                                    std::env::set_var("DISPLAY", format!(":{display_number}"));
+                                    app.common.comp.xwm = Some(xwm);
+                                    app.common.comp.xdisplay = Some(display_number);
+                                }
+                                Err(err) => error!("Unable to start X11 window manager: {err}"),
+                            }
+                        }
+                        XWaylandEvent::Error => warn!("Xwayland exited during startup"),
+                    });
+            if let Err(err) = result {
+                error!("Unable to insert Xwayland source: {}", err.error);
+            }
+        });
+    }
+
+    /// Returns the managed window of the X11 surface
+    fn x11_window(&self, surface: &X11Surface) -> Option<desktop::Window> {
+        self.common
+            .comp
+            .windows
+            .iter()
+            .find(|window| window.x11_surface() == Some(surface))
+            .cloned()
+    }
+
+    /// Forgets the X11 window, whether it was managed or not
+    fn remove_x11_window(&mut self, surface: &X11Surface) {
+        self.common
+            .comp
+            .override_redirect
+            .retain(|window| window != surface);
+        self.common.comp.schedule_redraw();
+
+        let Some(window) = self.x11_window(surface) else {
+            return;
+        };
+        self.unfocus(&window);
+
+        let comp = &mut self.common.comp;
+        comp.windows.retain(|other| other != &window);
+        if let Some(workspace) = comp.workspaces.containing_mut(&window) {
+            workspace.remove(&window);
+        }
+        comp.space.unmap_elem(&window);
+
+        let id = crate::ipc::window_id(&window);
+        comp.ipc
+            .emit(crate::ipc::protocol::Event::WindowClosed { id });
+    }
+
+    /// Returns the window of the X11 surface and the start data of the pointer grab it requested
+    fn x11_grab_start(
+        &self,
+        surface: &X11Surface,
+    ) -> Option<(desktop::Window, GrabStartData<Self>)> {
+        let pointer = self.common.comp.seat.get_pointer()?;
+        let window = self.x11_window(surface)?;
+        let start_data = self.client_grab_start(&pointer, &window)?;
+        Some((window, start_data))
+    }
+}
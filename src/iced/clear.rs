@@ -1,6 +1,13 @@
+/// Makes a region of the target transparent again, so what is drawn
+/// there does not blend with what the previous frame left behind
 #[derive(Debug)]
-pub struct ScissorPrimitive;
-impl iced_wgpu::Primitive for ScissorPrimitive {
+pub struct ClearPrimitive {
+    /// Region in physical pixels. The clip bounds iced passes are not used,
+    /// as they are not guaranteed to match the scissor of the layers drawn in the region
+    pub region: iced_core::Rectangle<u32>,
+}
+
+impl iced_wgpu::Primitive for ClearPrimitive {
     fn prepare(
         &self,
         device: &wgpu::Device,
@@ -10,17 +17,66 @@ impl iced_wgpu::Primitive for ScissorPrimitive {
         _bounds: &iced_core::Rectangle,
         _viewport: &iced_wgpu::graphics::Viewport,
     ) {
-        let shader = device.create_shader_module(wgpu::include_wgsl!("scissors.wgsl"));
+        if !storage.has::<Pipeline>() {
+            storage.store(Pipeline::new(device, format));
+        }
+    }
+
+    fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        storage: &iced_widget::shader::Storage,
+        target: &wgpu::TextureView,
+        _clip_bounds: &iced_core::Rectangle<u32>,
+    ) {
+        let Some(pipeline) = storage.get::<Pipeline>() else {
+            return;
+        };
+        if self.region.width == 0 || self.region.height == 0 {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&pipeline.render_pipeline);
+        render_pass.set_scissor_rect(
+            self.region.x,
+            self.region.y,
+            self.region.width,
+            self.region.height,
+        );
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+struct Pipeline {
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl Pipeline {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("clear.wgsl"));
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Scissors pipeline layout"),
+                label: Some("Clear pipeline layout"),
                 bind_group_layouts: &[],
                 push_constant_ranges: &[],
             });
 
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Scissors pipeline"),
+            label: Some("Clear pipeline"),
             layout: Some(&render_pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
@@ -33,60 +89,19 @@ impl iced_wgpu::Primitive for ScissorPrimitive {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format,
-                    blend: Some(wgpu::BlendState {
-                        alpha: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::Zero,
-                            dst_factor: wgpu::BlendFactor::Zero,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::Zero,
-                            dst_factor: wgpu::BlendFactor::Zero,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                    }),
+                    // Replaces the previous contents instead of blending with them
+                    blend: None,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                ..Default::default()
-            },
+            primitive: wgpu::PrimitiveState::default(),
             multisample: wgpu::MultisampleState::default(),
             depth_stencil: None,
             multiview: None,
             cache: None,
         });
 
-        storage.store(render_pipeline);
-    }
-
-    fn render(
-        &self,
-        encoder: &mut wgpu::CommandEncoder,
-        storage: &iced_widget::shader::Storage,
-        target: &wgpu::TextureView,
-        clip_bounds: &iced_core::Rectangle<u32>,
-    ) {
-
-
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Scissors render pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: target,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            ..Default::default()
-        });
-
-        render_pass.set_pipeline(storage.get().expect("No pipeline in storage"));
-        render_pass.set_scissor_rect(clip_bounds.x, clip_bounds.y, clip_bounds.width, clip_bounds.height);
-
-        render_pass.draw(0..4, 0..1);
+        Self { render_pipeline }
     }
 }
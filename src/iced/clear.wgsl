@@ -0,0 +1,11 @@
+// Covers the whole target with a single triangle, the scissor limits it to the region
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let corner = vec2<f32>(f32(in_vertex_index % 2u) * 4.0, f32(in_vertex_index / 2u) * 4.0);
+    return vec4<f32>(corner - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0);
+}
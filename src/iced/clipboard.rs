@@ -0,0 +1,42 @@
+use iced_core::clipboard::Kind;
+
+/// Clipboard serving the text of the compositor selections.
+/// Writes are kept until the [super::Program] can apply them
+#[derive(Debug, Default)]
+pub struct Clipboard {
+    standard: Option<String>,
+    primary: Option<String>,
+    writes: Vec<(Kind, String)>,
+}
+
+impl Clipboard {
+    /// Replaces the contents after the selection changed outside of the user interface
+    pub fn set(&mut self, kind: Kind, contents: Option<String>) {
+        *self.contents_mut(kind) = contents;
+    }
+
+    pub fn take_writes(&mut self) -> Vec<(Kind, String)> {
+        std::mem::take(&mut self.writes)
+    }
+
+    fn contents_mut(&mut self, kind: Kind) -> &mut Option<String> {
+        match kind {
+            Kind::Standard => &mut self.standard,
+            Kind::Primary => &mut self.primary,
+        }
+    }
+}
+
+impl iced_core::Clipboard for Clipboard {
+    fn read(&self, kind: Kind) -> Option<String> {
+        match kind {
+            Kind::Standard => self.standard.clone(),
+            Kind::Primary => self.primary.clone(),
+        }
+    }
+
+    fn write(&mut self, kind: Kind, contents: String) {
+        *self.contents_mut(kind) = Some(contents.clone());
+        self.writes.push((kind, contents));
+    }
+}
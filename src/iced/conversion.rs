@@ -0,0 +1,137 @@
+use iced_core::keyboard::{self, key::Named, Key};
+use smithay::{
+    backend::input::{Axis, ButtonState, InputBackend, KeyState, PointerAxisEvent},
+    input::keyboard::{Keysym, KeysymHandle, ModifiersState},
+};
+
+/// Linux input event codes, see `linux/input-event-codes.h`
+mod button_codes {
+    pub const BTN_LEFT: u32 = 0x110;
+    pub const BTN_RIGHT: u32 = 0x111;
+    pub const BTN_MIDDLE: u32 = 0x112;
+    pub const BTN_SIDE: u32 = 0x113;
+    pub const BTN_EXTRA: u32 = 0x114;
+}
+
+pub fn mouse_button(button: u32) -> iced_core::mouse::Button {
+    match button {
+        button_codes::BTN_LEFT => iced_core::mouse::Button::Left,
+        button_codes::BTN_RIGHT => iced_core::mouse::Button::Right,
+        button_codes::BTN_MIDDLE => iced_core::mouse::Button::Middle,
+        button_codes::BTN_SIDE => iced_core::mouse::Button::Back,
+        button_codes::BTN_EXTRA => iced_core::mouse::Button::Forward,
+        other => iced_core::mouse::Button::Other(other as u16),
+    }
+}
+
+pub fn mouse_button_event(button: u32, state: ButtonState) -> iced_core::Event {
+    let button = mouse_button(button);
+    iced_core::Event::Mouse(match state {
+        ButtonState::Pressed => iced_core::mouse::Event::ButtonPressed(button),
+        ButtonState::Released => iced_core::mouse::Event::ButtonReleased(button),
+    })
+}
+
+pub fn scroll_event<IB: InputBackend, E: PointerAxisEvent<IB>>(event: &E) -> iced_core::Event {
+    // Wayland scrolls down on positive values, iced the other way around
+    let delta = match (
+        event.amount_v120(Axis::Horizontal),
+        event.amount_v120(Axis::Vertical),
+    ) {
+        (None, None) => iced_core::mouse::ScrollDelta::Pixels {
+            x: -event.amount(Axis::Horizontal).unwrap_or(0.0) as f32,
+            y: -event.amount(Axis::Vertical).unwrap_or(0.0) as f32,
+        },
+        (x, y) => iced_core::mouse::ScrollDelta::Lines {
+            x: -x.unwrap_or(0.0) as f32 / 120.0,
+            y: -y.unwrap_or(0.0) as f32 / 120.0,
+        },
+    };
+
+    iced_core::Event::Mouse(iced_core::mouse::Event::WheelScrolled { delta })
+}
+
+pub fn modifiers(state: &ModifiersState) -> keyboard::Modifiers {
+    let mut modifiers = keyboard::Modifiers::empty();
+    modifiers.set(keyboard::Modifiers::SHIFT, state.shift);
+    modifiers.set(keyboard::Modifiers::CTRL, state.ctrl);
+    modifiers.set(keyboard::Modifiers::ALT, state.alt);
+    modifiers.set(keyboard::Modifiers::LOGO, state.logo);
+    modifiers
+}
+
+pub fn key(keysym: Keysym) -> Key {
+    let named = match keysym {
+        Keysym::Return | Keysym::KP_Enter => Named::Enter,
+        Keysym::Tab | Keysym::ISO_Left_Tab => Named::Tab,
+        Keysym::space => Named::Space,
+        Keysym::BackSpace => Named::Backspace,
+        Keysym::Delete | Keysym::KP_Delete => Named::Delete,
+        Keysym::Insert => Named::Insert,
+        Keysym::Escape => Named::Escape,
+        Keysym::Left | Keysym::KP_Left => Named::ArrowLeft,
+        Keysym::Right | Keysym::KP_Right => Named::ArrowRight,
+        Keysym::Up | Keysym::KP_Up => Named::ArrowUp,
+        Keysym::Down | Keysym::KP_Down => Named::ArrowDown,
+        Keysym::Home | Keysym::KP_Home => Named::Home,
+        Keysym::End | Keysym::KP_End => Named::End,
+        Keysym::Page_Up => Named::PageUp,
+        Keysym::Page_Down => Named::PageDown,
+        Keysym::Shift_L | Keysym::Shift_R => Named::Shift,
+        Keysym::Control_L | Keysym::Control_R => Named::Control,
+        Keysym::Alt_L | Keysym::Alt_R => Named::Alt,
+        Keysym::Super_L | Keysym::Super_R => Named::Super,
+        Keysym::Caps_Lock => Named::CapsLock,
+        Keysym::F1 => Named::F1,
+        Keysym::F2 => Named::F2,
+        Keysym::F3 => Named::F3,
+        Keysym::F4 => Named::F4,
+        Keysym::F5 => Named::F5,
+        Keysym::F6 => Named::F6,
+        Keysym::F7 => Named::F7,
+        Keysym::F8 => Named::F8,
+        Keysym::F9 => Named::F9,
+        Keysym::F10 => Named::F10,
+        Keysym::F11 => Named::F11,
+        Keysym::F12 => Named::F12,
+        _ => {
+            return keysym
+                .key_char()
+                .map(|char| Key::Character(char.to_string().into()))
+                .unwrap_or(Key::Unidentified)
+        }
+    };
+
+    Key::Named(named)
+}
+
+pub fn keyboard_event(
+    state: KeyState,
+    modifiers: &ModifiersState,
+    handle: &KeysymHandle<'_>,
+) -> iced_core::Event {
+    let modified_sym = handle.modified_sym();
+    let key = key(handle.raw_syms().first().copied().unwrap_or(modified_sym));
+    let modifiers = self::modifiers(modifiers);
+
+    iced_core::Event::Keyboard(match state {
+        KeyState::Pressed => keyboard::Event::KeyPressed {
+            key,
+            modified_key: self::key(modified_sym),
+            physical_key: keyboard::key::Physical::Unidentified(keyboard::key::NativeCode::Xkb(
+                handle.raw_code().raw(),
+            )),
+            location: keyboard::Location::Standard,
+            modifiers,
+            text: modified_sym
+                .key_char()
+                .filter(|char| !char.is_control())
+                .map(|char| char.to_string().into()),
+        },
+        KeyState::Released => keyboard::Event::KeyReleased {
+            key,
+            location: keyboard::Location::Standard,
+            modifiers,
+        },
+    })
+}
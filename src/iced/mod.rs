@@ -12,12 +12,16 @@ use smithay::{
 };
 use std::sync::Arc;
 
+mod clear;
+pub mod clipboard;
+pub mod conversion;
 mod drm;
-pub mod scissors;
+pub mod renderer;
+pub mod surface;
 pub mod texture;
 pub mod wgpu;
 
-pub type Renderer = iced_wgpu::Renderer;
+pub type Renderer = renderer::Renderer;
 pub type Theme = iced_core::Theme;
 pub type Element<'a, Message> = iced_core::Element<'a, Message, Theme, Renderer>;
 pub type Bounds = iced_core::Size<u32>;
@@ -25,12 +29,28 @@ pub type Bounds = iced_core::Size<u32>;
 pub trait Program {
     type Data;
     type Message: iced_runtime::futures::MaybeSend + 'static;
+    /// Information gathered from the laid out user interface
+    type Layout;
 
     fn view(data: &Self::Data) -> impl Into<Element<'_, Self::Message>>;
     fn update(
         data: &mut Self::Data,
         message: Self::Message,
     ) -> impl Into<iced_runtime::Task<Self::Message>>;
+
+    /// Called after every layout pass while the user interface still borrows the data
+    fn inspect_layout(
+        ui: &mut iced_runtime::UserInterface<'_, Self::Message, Theme, Renderer>,
+        renderer: &Renderer,
+    ) -> Self::Layout;
+    /// Called with the result of [Program::inspect_layout] once the data can be modified again
+    fn apply_layout(data: &mut Self::Data, layout: Self::Layout);
+
+    /// Called when the user interface wrote to the clipboard
+    fn write_clipboard(data: &mut Self::Data, kind: iced_core::clipboard::Kind, contents: String);
+
+    /// Called when the user interface changed or wants to be drawn again for an animation
+    fn request_redraw(data: &mut Self::Data, request: iced_core::window::RedrawRequest);
 }
 
 pub struct Driver<P: Program> {
@@ -38,10 +58,13 @@ pub struct Driver<P: Program> {
     wgpu_objects: Arc<wgpu::Objects>,
     engine: iced_wgpu::Engine,
     renderer: Renderer,
+    theme: Theme,
     allocator: GbmAllocator<DrmDeviceFd>,
     cached_buffer: Option<(Bounds, Dmabuf, Arc<wgpu::Texture>)>,
     task_scheduler: calloop::futures::Scheduler<Option<iced_runtime::Action<P::Message>>>,
     event_sender: calloop::channel::Sender<iced_core::Event>,
+    cursor: iced_core::mouse::Cursor,
+    clipboard: clipboard::Clipboard,
 }
 
 impl<P: Program + 'static> Driver<P> {
@@ -57,12 +80,12 @@ impl<P: Program + 'static> Driver<P> {
             wgpu::TextureFormat::Rgba8Unorm,
             None,
         );
-        let renderer = iced_wgpu::Renderer::new(
+        let renderer = Renderer::new(iced_wgpu::Renderer::new(
             &wgpu_objects.device,
             &engine,
             iced_core::Font::default(),
             16.into(),
-        );
+        ));
 
         let (task_executor, task_scheduler) =
             calloop::futures::executor::<Option<iced_runtime::Action<P::Message>>>()
@@ -103,13 +126,29 @@ impl<P: Program + 'static> Driver<P> {
             wgpu_objects,
             engine,
             renderer,
+            theme: Theme::CatppuccinMocha,
             allocator,
             cached_buffer: None,
             task_scheduler,
             event_sender,
+            cursor: iced_core::mouse::Cursor::Unavailable,
+            clipboard: Default::default(),
         }
     }
 
+    /// Changes the look of the user interface. The renderer is recreated for the new text size
+    pub fn set_theme(&mut self, theme: Theme, text_size: f32) {
+        self.theme = theme;
+        self.renderer = Renderer::new(iced_wgpu::Renderer::new(
+            &self.wgpu_objects.device,
+            &self.engine,
+            iced_core::Font::default(),
+            text_size.into(),
+        ));
+        // Cached layouts were measured with the previous text size
+        self.cache = Default::default();
+    }
+
     pub fn schedule_task(&self, task: iced_runtime::Task<P::Message>) {
         iced_runtime::task::into_stream(task)
             .map(|stream| stream.into_future())
@@ -124,6 +163,21 @@ impl<P: Program + 'static> Driver<P> {
     pub fn process_message(&mut self, data: &mut P::Data, message: P::Message) {
         let task = P::update(data, message).into();
         self.schedule_task(task);
+        P::request_redraw(data, iced_core::window::RedrawRequest::NextFrame);
+    }
+
+    /// Cursor position relative to the program bounds
+    pub fn cursor(&self) -> iced_core::mouse::Cursor {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: iced_core::mouse::Cursor) {
+        self.cursor = cursor;
+    }
+
+    /// Updates the clipboard contents after the selection changed elsewhere
+    pub fn set_clipboard(&mut self, kind: iced_core::clipboard::Kind, contents: Option<String>) {
+        self.clipboard.set(kind, contents);
     }
 
     pub fn schedule_event(&self, event: iced_core::Event) {
@@ -136,19 +190,37 @@ impl<P: Program + 'static> Driver<P> {
         data: &mut P::Data,
         bounds: Bounds,
         process_messages_immediately: bool,
+    ) {
+        self.update(&[event], data, bounds, process_messages_immediately);
+        // Widgets may look different after an event, like when hovered
+        P::request_redraw(data, iced_core::window::RedrawRequest::NextFrame);
+    }
+
+    /// Passes the events to the user interface and handles what it produced
+    fn update(
+        &mut self,
+        events: &[iced_core::Event],
+        data: &mut P::Data,
+        bounds: Bounds,
+        process_messages_immediately: bool,
     ) {
         let mut messages: Vec<P::Message> = Vec::new();
 
-        self.with_ui(data, bounds, |ui, driver| {
-            ui.update(
-                &[event],
-                iced_core::mouse::Cursor::Unavailable,
+        let state = self.with_ui(data, bounds, |ui, driver| {
+            let (state, _) = ui.update(
+                events,
+                driver.cursor,
                 &mut driver.renderer,
-                &mut iced_core::clipboard::Null,
+                &mut driver.clipboard,
                 &mut messages,
             );
+            state
         });
 
+        for (kind, contents) in self.clipboard.take_writes() {
+            P::write_clipboard(data, kind, contents);
+        }
+
         while let Some(message) = messages.pop() {
             if process_messages_immediately {
                 self.process_message(data, message);
@@ -156,21 +228,56 @@ impl<P: Program + 'static> Driver<P> {
                 self.schedule_message(message);
             }
         }
-    }
 
-    pub fn render(&mut self, data: &P::Data, bounds: Bounds) -> Dmabuf {
-        let (dmabuf, texture) = self.get_buffer(bounds);
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        if let iced_runtime::user_interface::State::Updated {
+            redraw_request: Some(request),
+        } = state
+        {
+            P::request_redraw(data, request);
+        }
+    }
 
-        self.with_ui(data, bounds, |ui, driver| {
-            ui.draw(
-                &mut driver.renderer,
-                &iced_core::Theme::CatppuccinMocha,
-                &iced_core::renderer::Style::default(),
-                iced_core::mouse::Cursor::Unavailable,
-            );
+    /// Renders the user interface laid out in the logical bounds.
+    /// The buffer has the physical size of the bounds at the scale factor.
+    /// Returns the buffer and the regions that changed since the last render in physical pixels
+    pub fn render(
+        &mut self,
+        data: &mut P::Data,
+        bounds: Bounds,
+        scale_factor: f64,
+    ) -> (Dmabuf, Vec<iced_core::Rectangle<u32>>) {
+        // Animations advance and request their next frame when told about the redraw
+        let now = iced_core::window::Event::RedrawRequested(std::time::Instant::now());
+        self.update(&[iced_core::Event::Window(now)], data, bounds, false);
+
+        let physical_size = physical_size(bounds, scale_factor);
+        let (dmabuf, texture, allocated) = self.get_buffer(physical_size);
+        let viewport =
+            iced_wgpu::graphics::Viewport::with_physical_size(physical_size, scale_factor);
+
+        // Drawing once records what changed, drawing again passes the changed regions on to wgpu
+        let regions = self.with_ui(data, bounds, |ui, driver| {
+            let style = iced_core::renderer::Style::default();
+            ui.draw(&mut driver.renderer, &driver.theme, &style, driver.cursor);
+
+            // A new buffer does not hold the previous frame, so all of it is damaged
+            let regions = driver.renderer.damage(&viewport, allocated);
+            for &region in &regions {
+                driver.renderer.start_region(region, &viewport);
+                ui.draw(&mut driver.renderer, &driver.theme, &style, driver.cursor);
+                driver.renderer.end_region();
+            }
+            regions
         });
 
+        let damage: Vec<_> = regions
+            .into_iter()
+            .filter_map(|region| renderer::scissor(region, &viewport))
+            .collect();
+        if damage.is_empty() {
+            return (dmabuf, damage);
+        }
+
         let mut encoder =
             self.wgpu_objects
                 .device
@@ -178,16 +285,13 @@ impl<P: Program + 'static> Driver<P> {
                     label: Some("Iced render encoder"),
                 });
 
+        // Only the damaged regions of the buffer change, the rest still shows the previous frame
         self.renderer.present(
             &mut self.engine,
-            &self.wgpu_objects.device,
-            &self.wgpu_objects.queue,
+            &self.wgpu_objects,
             &mut encoder,
-            Some(iced_core::color!(0x6666aa)),
-            texture::properties::TEXTURE_FORMAT.1,
-            &texture_view,
-            &iced_wgpu::graphics::Viewport::with_physical_size(bounds, 1.0),
-            &[] as &[String],
+            &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &viewport,
         );
 
         let submission_index = self.engine.submit(&self.wgpu_objects.queue, encoder);
@@ -195,41 +299,45 @@ impl<P: Program + 'static> Driver<P> {
             .device
             .poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
 
-        dmabuf
+        (dmabuf, damage)
     }
 
-    fn get_buffer(&mut self, bounds: Bounds) -> (Dmabuf, Arc<wgpu::Texture>) {
-        let new_cached_buffer = self
+    /// Returns the buffer for the bounds and whether it was newly allocated
+    fn get_buffer(&mut self, bounds: Bounds) -> (Dmabuf, Arc<wgpu::Texture>, bool) {
+        let cached_buffer = self
             .cached_buffer
-            .take_if(|(cached_bounds, _, _)| cached_bounds == &bounds)
-            .unwrap_or_else(|| {
-                let gbm_buffer = self
-                    .allocator
-                    .create_buffer(
-                        bounds.width,
-                        bounds.height,
-                        texture::properties::TEXTURE_FORMAT.2,
-                        &[drm_fourcc::DrmModifier::Linear],
-                    )
-                    .expect("Unable to allocate gbm buffer");
-
-                let dmabuf = gbm_buffer
-                    .export()
-                    .expect("Unable to export gbm buffer as dmabuf");
-
-                let texture = unsafe { texture::from_dmabuf(&self.wgpu_objects.device, &dmabuf) };
-
-                (bounds, dmabuf, Arc::new(texture))
-            });
+            .take_if(|(cached_bounds, _, _)| cached_bounds == &bounds);
+        let allocated = cached_buffer.is_none();
+
+        let new_cached_buffer = cached_buffer.unwrap_or_else(|| {
+            let gbm_buffer = self
+                .allocator
+                .create_buffer(
+                    bounds.width,
+                    bounds.height,
+                    texture::properties::TEXTURE_FORMAT.2,
+                    &[drm_fourcc::DrmModifier::Linear],
+                )
+                .expect("Unable to allocate gbm buffer");
+
+            let dmabuf = gbm_buffer
+                .export()
+                .expect("Unable to export gbm buffer as dmabuf");
+
+            let texture = unsafe { texture::from_dmabuf(&self.wgpu_objects.device, &dmabuf) }
+                .expect("Unable to import the shell dmabuf");
+
+            (bounds, dmabuf, Arc::new(texture))
+        });
 
         self.cached_buffer = Some(new_cached_buffer.clone());
 
-        (new_cached_buffer.1, new_cached_buffer.2)
+        (new_cached_buffer.1, new_cached_buffer.2, allocated)
     }
 
     fn with_ui<T>(
         &mut self,
-        data: &P::Data,
+        data: &mut P::Data,
         bounds: Bounds,
         func: impl FnOnce(&mut iced_runtime::UserInterface<P::Message, Theme, Renderer>, &mut Self) -> T,
     ) -> T {
@@ -240,8 +348,20 @@ impl<P: Program + 'static> Driver<P> {
             &mut self.renderer,
         );
         let result = func(&mut ui, self);
+        let layout = P::inspect_layout(&mut ui, &self.renderer);
         self.cache = ui.into_cache();
 
+        P::apply_layout(data, layout);
+
         result
     }
 }
+
+/// Size of the buffer the bounds are rendered into
+pub fn physical_size(bounds: Bounds, scale_factor: f64) -> Bounds {
+    (
+        (bounds.width as f64 * scale_factor).round() as u32,
+        (bounds.height as f64 * scale_factor).round() as u32,
+    )
+        .into()
+}
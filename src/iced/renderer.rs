@@ -0,0 +1,548 @@
+use super::{clear::ClearPrimitive, surface::SurfacePrimitive};
+use iced_core::{
+    alignment, renderer::Quad, text, text::Renderer as _, Background, Color, Font, Pixels, Point,
+    Rectangle, Renderer as _, Size, Transformation, Vector,
+};
+use iced_wgpu::graphics::{
+    damage,
+    text::{editor, paragraph, Editor, Paragraph},
+    Viewport,
+};
+use iced_wgpu::primitive::Renderer as _;
+use std::any::Any;
+
+/// Renderer of the user interface, which is drawn twice per frame.
+/// The first time only records what is drawn. Comparing it with the previous frame results in
+/// the damaged regions, which the wgpu renderer draws the second time
+pub struct Renderer {
+    wgpu: iced_wgpu::Renderer,
+    /// Damaged region drawing is passed on for. Drawing is recorded without one
+    region: Option<Rectangle>,
+    /// Items drawn in the frame being recorded
+    items: Vec<Item>,
+    /// Items of the previous frame with the physical size and scale factor it was drawn at
+    previous: Option<(Vec<Item>, Size<u32>, f64)>,
+    /// Bounds of the open layers after their transformation, innermost last
+    layers: Vec<Rectangle>,
+    /// Transformations combined with the ones before them, innermost last
+    transformations: Vec<Transformation>,
+}
+
+impl Renderer {
+    pub fn new(wgpu: iced_wgpu::Renderer) -> Self {
+        Self {
+            wgpu,
+            region: None,
+            items: Vec::new(),
+            previous: None,
+            layers: Vec::new(),
+            transformations: Vec::new(),
+        }
+    }
+
+    /// Regions in logical coordinates that differ between the recorded frame and the previous
+    /// one. All of the viewport is damaged if it changed or the target does not hold the
+    /// previous frame anymore
+    pub fn damage(&mut self, viewport: &Viewport, redraw: bool) -> Vec<Rectangle> {
+        let items = std::mem::take(&mut self.items);
+        let bounds = Rectangle::with_size(viewport.logical_size());
+
+        let damage = match &self.previous {
+            Some((previous, size, scale_factor))
+                if !redraw
+                    && *size == viewport.physical_size()
+                    && *scale_factor == viewport.scale_factor() =>
+            {
+                let damage = damage::diff(
+                    previous,
+                    &items,
+                    |item| item.bounds().into_iter().collect(),
+                    Item::damage,
+                );
+                // Antialiasing and glyphs can reach slightly past the bounds
+                let damage = damage.into_iter().map(|region| region.expand(1.)).collect();
+                damage::group(damage, bounds)
+            }
+            _ => vec![bounds],
+        };
+
+        self.previous = Some((items, viewport.physical_size(), viewport.scale_factor()));
+        // Only the damaged regions are drawn into the layers of the wgpu renderer
+        self.wgpu.clear();
+
+        damage
+    }
+
+    /// Passes drawing on to the wgpu renderer until [Renderer::end_region], clipped to the
+    /// region. The region is cleared first, so regions may overlap
+    pub fn start_region(&mut self, region: Rectangle, viewport: &Viewport) {
+        self.wgpu.start_layer(region);
+        self.wgpu.draw_primitive(
+            region,
+            ClearPrimitive {
+                region: scissor(region, viewport).unwrap_or_default(),
+            },
+        );
+        self.wgpu.end_layer();
+
+        self.wgpu.start_layer(region);
+        self.region = Some(region);
+    }
+
+    pub fn end_region(&mut self) {
+        self.wgpu.end_layer();
+        self.region = None;
+    }
+
+    /// Draws the damaged regions of the last frame into the target, keeping the rest of it
+    pub fn present(
+        &mut self,
+        engine: &mut iced_wgpu::Engine,
+        wgpu_objects: &super::wgpu::Objects,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        viewport: &Viewport,
+    ) {
+        self.wgpu.present(
+            engine,
+            &wgpu_objects.device,
+            &wgpu_objects.queue,
+            encoder,
+            None,
+            super::texture::properties::TEXTURE_FORMAT.1,
+            target,
+            viewport,
+            &[] as &[String],
+        );
+    }
+
+    fn transformation(&self) -> Transformation {
+        self.transformations
+            .last()
+            .copied()
+            .unwrap_or(Transformation::IDENTITY)
+    }
+
+    fn record(&mut self, kind: Kind) {
+        self.items.push(Item {
+            kind,
+            transformation: self.transformation(),
+            clip: self.layers.last().copied().unwrap_or(Rectangle::INFINITE),
+        });
+    }
+}
+
+/// Pixels the wgpu renderer draws a layer with the bounds into, computed the same way it does
+pub fn scissor(bounds: Rectangle, viewport: &Viewport) -> Option<Rectangle<u32>> {
+    let scale = Transformation::scale(viewport.scale_factor() as f32);
+    Rectangle::<f32>::from(Rectangle::with_size(viewport.physical_size()))
+        .intersection(&(bounds * scale))
+        .and_then(Rectangle::snap)
+}
+
+/// Reverses the transformation, which only scales and translates
+fn untransformed(bounds: Rectangle, transformation: Transformation) -> Rectangle {
+    let scale = transformation.scale_factor();
+    let translation = transformation.translation();
+    Rectangle {
+        x: (bounds.x - translation.x) / scale,
+        y: (bounds.y - translation.y) / scale,
+        width: bounds.width / scale,
+        height: bounds.height / scale,
+    }
+}
+
+/// Moves the bounds of text from its anchor to where it is drawn
+fn aligned(
+    mut bounds: Rectangle,
+    horizontal: alignment::Horizontal,
+    vertical: alignment::Vertical,
+) -> Rectangle {
+    match horizontal {
+        alignment::Horizontal::Left => {}
+        alignment::Horizontal::Center => bounds.x -= bounds.width / 2.,
+        alignment::Horizontal::Right => bounds.x -= bounds.width,
+    }
+    match vertical {
+        alignment::Vertical::Top => {}
+        alignment::Vertical::Center => bounds.y -= bounds.height / 2.,
+        alignment::Vertical::Bottom => bounds.y -= bounds.height,
+    }
+    bounds
+}
+
+/// Something drawn in a frame
+struct Item {
+    kind: Kind,
+    transformation: Transformation,
+    /// Bounds of the layer it was drawn in
+    clip: Rectangle,
+}
+
+impl Item {
+    /// Part of the frame the bounds cover once transformed and clipped like the item
+    fn visible(&self, bounds: Rectangle) -> Option<Rectangle> {
+        (bounds * self.transformation).intersection(&self.clip)
+    }
+
+    fn bounds(&self) -> Option<Rectangle> {
+        self.visible(self.kind.bounds()?)
+    }
+
+    /// Regions that changed between the items drawn at the same position in two frames
+    fn damage(previous: &Self, current: &Self) -> Vec<Rectangle> {
+        if previous.transformation == current.transformation && previous.clip == current.clip {
+            match (&previous.kind, &current.kind) {
+                // Only the surfaces of a window that were committed to are drawn again
+                (
+                    Kind::Surface {
+                        bounds: previous_bounds,
+                        primitive: previous_primitive,
+                    },
+                    Kind::Surface { bounds, primitive },
+                ) if previous_bounds == bounds => {
+                    if let Some(damage) = primitive.damage(previous_primitive) {
+                        let offset = Vector::new(bounds.x, bounds.y);
+                        return damage
+                            .into_iter()
+                            .filter_map(|region| current.visible(region + offset))
+                            .collect();
+                    }
+                }
+                (previous_kind, kind) if previous_kind.same(kind) => return Vec::new(),
+                _ => {}
+            }
+        }
+
+        previous
+            .bounds()
+            .into_iter()
+            .chain(current.bounds())
+            .collect()
+    }
+}
+
+enum Kind {
+    Quad(Quad, Background),
+    /// The weak references keep the text from being modified in place,
+    /// so comparing the buffers tells whether it changed
+    Paragraph {
+        paragraph: paragraph::Weak,
+        position: Point,
+        color: Color,
+        clip_bounds: Rectangle,
+    },
+    Editor {
+        editor: editor::Weak,
+        position: Point,
+        color: Color,
+        clip_bounds: Rectangle,
+    },
+    Text {
+        text: text::Text,
+        position: Point,
+        color: Color,
+        clip_bounds: Rectangle,
+    },
+    Surface {
+        bounds: Rectangle,
+        primitive: SurfacePrimitive,
+    },
+    /// Primitives of other kinds, which are assumed to change every frame
+    Primitive {
+        bounds: Rectangle,
+    },
+}
+
+impl Kind {
+    /// Bounds before the transformation
+    fn bounds(&self) -> Option<Rectangle> {
+        match self {
+            Self::Quad(quad, _) => {
+                let shadow = quad.shadow;
+                if shadow.color.a == 0. {
+                    return Some(quad.bounds);
+                }
+                let shadow_bounds = (quad.bounds + shadow.offset).expand(shadow.blur_radius);
+                Some(quad.bounds.union(&shadow_bounds))
+            }
+            Self::Paragraph {
+                paragraph,
+                position,
+                clip_bounds,
+                ..
+            } => aligned(
+                Rectangle::new(*position, paragraph.min_bounds),
+                paragraph.horizontal_alignment,
+                paragraph.vertical_alignment,
+            )
+            .intersection(clip_bounds),
+            Self::Editor {
+                editor,
+                position,
+                clip_bounds,
+                ..
+            } => Rectangle::new(*position, editor.bounds).intersection(clip_bounds),
+            Self::Text {
+                text,
+                position,
+                clip_bounds,
+                ..
+            } => aligned(
+                Rectangle::new(*position, text.bounds),
+                text.horizontal_alignment,
+                text.vertical_alignment,
+            )
+            .intersection(clip_bounds),
+            Self::Surface { bounds, .. } | Self::Primitive { bounds } => Some(*bounds),
+        }
+    }
+
+    /// Whether both draw the same
+    fn same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Quad(quad, background), Self::Quad(other_quad, other_background)) => {
+                quad == other_quad && background == other_background
+            }
+            (
+                Self::Paragraph {
+                    paragraph,
+                    position,
+                    color,
+                    clip_bounds,
+                },
+                Self::Paragraph {
+                    paragraph: other_paragraph,
+                    position: other_position,
+                    color: other_color,
+                    clip_bounds: other_clip_bounds,
+                },
+            ) => {
+                position == other_position
+                    && color == other_color
+                    && clip_bounds == other_clip_bounds
+                    && paragraph
+                        .upgrade()
+                        .zip(other_paragraph.upgrade())
+                        .is_some_and(|(paragraph, other)| {
+                            std::ptr::eq(paragraph.buffer(), other.buffer())
+                        })
+            }
+            (
+                Self::Editor {
+                    editor,
+                    position,
+                    color,
+                    clip_bounds,
+                },
+                Self::Editor {
+                    editor: other_editor,
+                    position: other_position,
+                    color: other_color,
+                    clip_bounds: other_clip_bounds,
+                },
+            ) => {
+                position == other_position
+                    && color == other_color
+                    && clip_bounds == other_clip_bounds
+                    && editor.upgrade().zip(other_editor.upgrade()).is_some_and(
+                        |(editor, other)| std::ptr::eq(editor.buffer(), other.buffer()),
+                    )
+            }
+            (
+                Self::Text {
+                    text,
+                    position,
+                    color,
+                    clip_bounds,
+                },
+                Self::Text {
+                    text: other_text,
+                    position: other_position,
+                    color: other_color,
+                    clip_bounds: other_clip_bounds,
+                },
+            ) => {
+                position == other_position
+                    && color == other_color
+                    && clip_bounds == other_clip_bounds
+                    && same_text(text, other_text)
+            }
+            (
+                Self::Surface { bounds, primitive },
+                Self::Surface {
+                    bounds: other_bounds,
+                    primitive: other_primitive,
+                },
+            ) => {
+                bounds == other_bounds
+                    && primitive
+                        .damage(other_primitive)
+                        .is_some_and(|damage| damage.is_empty())
+            }
+            _ => false,
+        }
+    }
+}
+
+fn same_text(text: &text::Text, other: &text::Text) -> bool {
+    text.content == other.content
+        && text.bounds == other.bounds
+        && text.size == other.size
+        && text.line_height == other.line_height
+        && text.font == other.font
+        && text.horizontal_alignment == other.horizontal_alignment
+        && text.vertical_alignment == other.vertical_alignment
+        && text.shaping == other.shaping
+        && text.wrapping == other.wrapping
+}
+
+impl iced_core::Renderer for Renderer {
+    fn start_layer(&mut self, bounds: Rectangle) {
+        let transformation = self.transformation();
+        let bounds = bounds * transformation;
+
+        // Layers are not clipped to their parent, but all of them are clipped to the region
+        if let Some(region) = self.region {
+            let clipped = bounds
+                .intersection(&region)
+                .unwrap_or(Rectangle::new(region.position(), Size::ZERO));
+            self.wgpu
+                .start_layer(untransformed(clipped, transformation));
+        }
+        self.layers.push(bounds);
+    }
+
+    fn end_layer(&mut self) {
+        self.layers.pop();
+        if self.region.is_some() {
+            self.wgpu.end_layer();
+        }
+    }
+
+    fn start_transformation(&mut self, transformation: Transformation) {
+        self.transformations
+            .push(self.transformation() * transformation);
+        if self.region.is_some() {
+            self.wgpu.start_transformation(transformation);
+        }
+    }
+
+    fn end_transformation(&mut self) {
+        self.transformations.pop();
+        if self.region.is_some() {
+            self.wgpu.end_transformation();
+        }
+    }
+
+    fn fill_quad(&mut self, quad: Quad, background: impl Into<Background>) {
+        let background = background.into();
+        match self.region {
+            Some(_) => self.wgpu.fill_quad(quad, background),
+            None => self.record(Kind::Quad(quad, background)),
+        }
+    }
+
+    /// Called every time the user interface is drawn.
+    /// The layers of the wgpu renderer are cleared once the damage is known
+    fn clear(&mut self) {
+        self.layers.clear();
+        self.transformations.clear();
+        if self.region.is_none() {
+            self.items.clear();
+        }
+    }
+}
+
+impl text::Renderer for Renderer {
+    type Font = Font;
+    type Paragraph = Paragraph;
+    type Editor = Editor;
+
+    const ICON_FONT: Font = <iced_wgpu::Renderer as text::Renderer>::ICON_FONT;
+    const CHECKMARK_ICON: char = <iced_wgpu::Renderer as text::Renderer>::CHECKMARK_ICON;
+    const ARROW_DOWN_ICON: char = <iced_wgpu::Renderer as text::Renderer>::ARROW_DOWN_ICON;
+
+    fn default_font(&self) -> Font {
+        self.wgpu.default_font()
+    }
+
+    fn default_size(&self) -> Pixels {
+        self.wgpu.default_size()
+    }
+
+    fn fill_paragraph(
+        &mut self,
+        paragraph: &Paragraph,
+        position: Point,
+        color: Color,
+        clip_bounds: Rectangle,
+    ) {
+        match self.region {
+            Some(_) => self
+                .wgpu
+                .fill_paragraph(paragraph, position, color, clip_bounds),
+            None => self.record(Kind::Paragraph {
+                paragraph: paragraph.downgrade(),
+                position,
+                color,
+                clip_bounds,
+            }),
+        }
+    }
+
+    fn fill_editor(
+        &mut self,
+        editor: &Editor,
+        position: Point,
+        color: Color,
+        clip_bounds: Rectangle,
+    ) {
+        match self.region {
+            Some(_) => self.wgpu.fill_editor(editor, position, color, clip_bounds),
+            None => self.record(Kind::Editor {
+                editor: editor.downgrade(),
+                position,
+                color,
+                clip_bounds,
+            }),
+        }
+    }
+
+    fn fill_text(
+        &mut self,
+        text: text::Text,
+        position: Point,
+        color: Color,
+        clip_bounds: Rectangle,
+    ) {
+        match self.region {
+            Some(_) => self.wgpu.fill_text(text, position, color, clip_bounds),
+            None => self.record(Kind::Text {
+                text,
+                position,
+                color,
+                clip_bounds,
+            }),
+        }
+    }
+}
+
+impl iced_wgpu::primitive::Renderer for Renderer {
+    fn draw_primitive(&mut self, bounds: Rectangle, primitive: impl iced_wgpu::Primitive) {
+        if self.region.is_some() {
+            self.wgpu.draw_primitive(bounds, primitive);
+            return;
+        }
+
+        let primitive: Box<dyn Any> = Box::new(primitive);
+        let kind = match primitive.downcast::<SurfacePrimitive>() {
+            Ok(primitive) => Kind::Surface {
+                bounds,
+                primitive: *primitive,
+            },
+            Err(_) => Kind::Primitive { bounds },
+        };
+        self.record(kind);
+    }
+}
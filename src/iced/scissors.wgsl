@@ -1,13 +0,0 @@
-@vertex
-fn vs_main(
-    @builtin(vertex_index) in_vertex_index: u32,
-) -> @builtin(position) vec4<f32> {
-    let x = f32(i32(in_vertex_index % 2) * 2 - 1);
-    let y = f32(i32(in_vertex_index / 2) * 2 - 1);
-    return vec4<f32>(x, y, 0.0, 1.0);
-}
-
-@fragment
-fn fs_main(@builtin(position) clip_position: vec4<f32>) -> @location(0) vec4<f32> {
-    return vec4<f32>(1.0, 1.0, 1.0, 0.1);
-}
@@ -0,0 +1,544 @@
+use smithay::{
+    backend::renderer::{
+        buffer_type,
+        utils::{CommitCounter, RendererSurfaceStateUserData},
+        BufferType,
+    },
+    reexports::wayland_server::{
+        backend::ObjectId,
+        protocol::{wl_buffer::WlBuffer, wl_shm, wl_surface::WlSurface},
+        Resource,
+    },
+    utils::{Logical, Point},
+    wayland::{
+        compositor::{self, TraversalAction},
+        dmabuf, shm,
+    },
+};
+use std::{collections::HashMap, sync::Arc};
+use tracing::warn;
+
+/// Size of the uniforms in the shader, including padding
+const UNIFORMS_SIZE: usize = 12;
+
+/// Surface of a client surface tree
+#[derive(Debug)]
+struct Surface {
+    surface: WlSurface,
+    buffer: WlBuffer,
+    commit: CommitCounter,
+    /// Where the surface is drawn relative to the primitive bounds
+    dst: iced_core::Rectangle,
+    /// Part of the buffer that is drawn in texture coordinates
+    src: iced_core::Rectangle,
+}
+
+/// Draws the buffers of a client surface tree
+#[derive(Debug)]
+pub struct SurfacePrimitive {
+    /// Bottommost surface first
+    surfaces: Vec<Surface>,
+}
+
+impl SurfacePrimitive {
+    /// Collects the surfaces of the tree that have a buffer attached.
+    /// The location is where the root surface is placed relative to the primitive bounds
+    pub fn new(root: &WlSurface, location: Point<i32, Logical>) -> Self {
+        let mut surfaces = Vec::new();
+
+        compositor::with_surface_tree_upward(
+            root,
+            location,
+            |_, states, location| {
+                let view = states
+                    .data_map
+                    .get::<RendererSurfaceStateUserData>()
+                    .and_then(|data| data.lock().unwrap().view());
+
+                match view {
+                    Some(view) => TraversalAction::DoChildren(*location + view.offset),
+                    None => TraversalAction::SkipChildren,
+                }
+            },
+            |surface, states, location| {
+                let Some(data) = states.data_map.get::<RendererSurfaceStateUserData>() else {
+                    return;
+                };
+                let data = data.lock().unwrap();
+                let (Some(view), Some(buffer), Some(surface_size)) =
+                    (data.view(), data.buffer(), data.surface_size())
+                else {
+                    return;
+                };
+
+                let location = *location + view.offset;
+                surfaces.push(Surface {
+                    surface: surface.clone(),
+                    buffer: WlBuffer::clone(buffer),
+                    commit: data.current_commit(),
+                    dst: iced_core::Rectangle::new(
+                        iced_core::Point::new(location.x as f32, location.y as f32),
+                        iced_core::Size::new(view.dst.w as f32, view.dst.h as f32),
+                    ),
+                    src: iced_core::Rectangle::new(
+                        iced_core::Point::new(
+                            (view.src.loc.x / surface_size.w as f64) as f32,
+                            (view.src.loc.y / surface_size.h as f64) as f32,
+                        ),
+                        iced_core::Size::new(
+                            (view.src.size.w / surface_size.w as f64) as f32,
+                            (view.src.size.h / surface_size.h as f64) as f32,
+                        ),
+                    ),
+                });
+            },
+            |_, _, _| true,
+        );
+
+        Self { surfaces }
+    }
+
+    /// Regions relative to the primitive bounds whose surfaces changed since the previous
+    /// primitive was drawn. Returns [None] if the primitives do not draw the same surfaces
+    pub fn damage(&self, previous: &Self) -> Option<Vec<iced_core::Rectangle>> {
+        if self.surfaces.len() != previous.surfaces.len() {
+            return None;
+        }
+
+        let mut damage = Vec::new();
+        for (surface, previous) in self.surfaces.iter().zip(&previous.surfaces) {
+            if surface.surface != previous.surface {
+                return None;
+            }
+            if surface.dst != previous.dst {
+                damage.extend([surface.dst, previous.dst]);
+            } else if surface.commit != previous.commit
+                || surface.buffer != previous.buffer
+                || surface.src != previous.src
+            {
+                damage.push(surface.dst);
+            }
+        }
+
+        Some(damage)
+    }
+}
+
+impl iced_wgpu::Primitive for SurfacePrimitive {
+    fn prepare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        storage: &mut iced_widget::shader::Storage,
+        bounds: &iced_core::Rectangle,
+        viewport: &iced_wgpu::graphics::Viewport,
+    ) {
+        if !storage.has::<Pipeline>() {
+            storage.store(Pipeline::new(device, format));
+        }
+        let pipeline = storage
+            .get_mut::<Pipeline>()
+            .expect("No pipeline in storage");
+
+        pipeline
+            .textures
+            .retain(|_, texture| texture.surface.is_alive());
+        pipeline
+            .dmabufs
+            .retain(|_, dmabuf| dmabuf.buffer.is_alive());
+
+        let scale = viewport.scale_factor() as f32;
+        let target_size = viewport.physical_size();
+        let (target_width, target_height) = (target_size.width as f32, target_size.height as f32);
+
+        for surface in &self.surfaces {
+            let Some(texture) = pipeline.update_texture(device, queue, surface) else {
+                continue;
+            };
+
+            // Clip space goes from -1 to 1 with the y axis pointing up
+            let x = (bounds.x + surface.dst.x) * scale / target_width * 2.0 - 1.0;
+            let y = 1.0 - (bounds.y + surface.dst.y) * scale / target_height * 2.0;
+            let width = surface.dst.width * scale / target_width * 2.0;
+            let height = -surface.dst.height * scale / target_height * 2.0;
+
+            let opaque = if texture.opaque { 1.0 } else { 0.0 };
+            let uniforms: [f32; UNIFORMS_SIZE] = [
+                x,
+                y,
+                width,
+                height,
+                surface.src.x,
+                surface.src.y,
+                surface.src.width,
+                surface.src.height,
+                opaque,
+                0.0,
+                0.0,
+                0.0,
+            ];
+            let uniforms: Vec<u8> = uniforms
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect();
+
+            queue.write_buffer(&texture.uniforms, 0, &uniforms);
+        }
+    }
+
+    fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        storage: &iced_widget::shader::Storage,
+        target: &wgpu::TextureView,
+        clip_bounds: &iced_core::Rectangle<u32>,
+    ) {
+        let Some(pipeline) = storage.get::<Pipeline>() else {
+            return;
+        };
+        if clip_bounds.width == 0 || clip_bounds.height == 0 {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Surface render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&pipeline.render_pipeline);
+        render_pass.set_scissor_rect(
+            clip_bounds.x,
+            clip_bounds.y,
+            clip_bounds.width,
+            clip_bounds.height,
+        );
+
+        for surface in &self.surfaces {
+            if let Some(texture) = pipeline.textures.get(&surface.surface.id()) {
+                render_pass.set_bind_group(0, &texture.bind_group, &[]);
+                render_pass.draw(0..4, 0..1);
+            }
+        }
+    }
+}
+
+/// Texture holding the contents of a surface buffer
+struct SurfaceTexture {
+    surface: WlSurface,
+    buffer: WlBuffer,
+    commit: CommitCounter,
+    texture: Arc<wgpu::Texture>,
+    /// Whether the alpha channel is padding
+    opaque: bool,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Texture sharing the memory of a client dmabuf, kept while the buffer exists.
+/// Clients usually cycle through a few buffers, which only have to be imported once
+struct DmabufTexture {
+    buffer: WlBuffer,
+    /// Texture and whether the alpha channel is padding.
+    /// [None] if the import failed, so it is not retried on every attach
+    imported: Option<(Arc<wgpu::Texture>, bool)>,
+}
+
+struct Pipeline {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// Textures by surface
+    textures: HashMap<ObjectId, SurfaceTexture>,
+    /// Imported dmabufs by buffer
+    dmabufs: HashMap<ObjectId, DmabufTexture>,
+}
+
+impl Pipeline {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("surface.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Surface bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Surface pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Surface pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // Client buffers have their alpha premultiplied
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            multisample: wgpu::MultisampleState::default(),
+            depth_stencil: None,
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Surface sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            render_pipeline,
+            bind_group_layout,
+            sampler,
+            textures: HashMap::new(),
+            dmabufs: HashMap::new(),
+        }
+    }
+
+    /// Imports the buffer of the surface unless the texture is still up to date
+    fn update_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface: &Surface,
+    ) -> Option<&SurfaceTexture> {
+        let id = surface.surface.id();
+
+        let buffer_type = buffer_type(&surface.buffer);
+        // Dmabufs are shared with the client, so only a new buffer has to be imported
+        let shared = matches!(buffer_type, Some(BufferType::Dma));
+        let up_to_date = self.textures.get(&id).is_some_and(|texture| {
+            texture.buffer == surface.buffer && (shared || texture.commit == surface.commit)
+        });
+
+        if !up_to_date {
+            let previous = self.textures.remove(&id);
+            let imported = match buffer_type {
+                Some(BufferType::Shm) => import_shm(
+                    device,
+                    queue,
+                    &surface.buffer,
+                    previous.as_ref().map(|previous| previous.texture.clone()),
+                ),
+                Some(BufferType::Dma) => self.dmabuf_texture(device, &surface.buffer),
+                _ => None,
+            };
+            let (texture, opaque) = imported?;
+
+            let uniforms = previous
+                .map(|previous| previous.uniforms)
+                .unwrap_or_else(|| {
+                    device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Surface uniforms"),
+                        size: (UNIFORMS_SIZE * std::mem::size_of::<f32>()) as u64,
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    })
+                });
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Surface bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniforms.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+            self.textures.insert(
+                id.clone(),
+                SurfaceTexture {
+                    surface: surface.surface.clone(),
+                    buffer: surface.buffer.clone(),
+                    commit: surface.commit,
+                    texture,
+                    opaque,
+                    uniforms,
+                    bind_group,
+                },
+            );
+        }
+
+        self.textures.get(&id)
+    }
+
+    /// Imports the dmabuf of the buffer unless it was imported before
+    fn dmabuf_texture(
+        &mut self,
+        device: &wgpu::Device,
+        buffer: &WlBuffer,
+    ) -> Option<(Arc<wgpu::Texture>, bool)> {
+        let dmabuf = self
+            .dmabufs
+            .entry(buffer.id())
+            .or_insert_with(|| DmabufTexture {
+                buffer: buffer.clone(),
+                imported: import_dmabuf(device, buffer),
+            });
+        dmabuf.imported.clone()
+    }
+}
+
+/// Uploads the shm buffer, reusing the previous texture if it still fits
+fn import_shm(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &WlBuffer,
+    previous: Option<Arc<wgpu::Texture>>,
+) -> Option<(Arc<wgpu::Texture>, bool)> {
+    let imported = shm::with_buffer_contents(buffer, |ptr, len, data| {
+        let (format, opaque) = match data.format {
+            wl_shm::Format::Argb8888 => (wgpu::TextureFormat::Bgra8Unorm, false),
+            wl_shm::Format::Xrgb8888 => (wgpu::TextureFormat::Bgra8Unorm, true),
+            wl_shm::Format::Abgr8888 => (wgpu::TextureFormat::Rgba8Unorm, false),
+            wl_shm::Format::Xbgr8888 => (wgpu::TextureFormat::Rgba8Unorm, true),
+            format => {
+                warn!("Unsupported shm format {format:?}");
+                return None;
+            }
+        };
+
+        let offset = data.offset as usize;
+        let length = data.stride as usize * data.height as usize;
+        if offset + length > len {
+            warn!("Shm buffer exceeds its pool");
+            return None;
+        }
+        // The pool stays mapped while in the closure and the range is inside the pool
+        let contents = unsafe { std::slice::from_raw_parts(ptr.add(offset), length) };
+
+        let size = wgpu::Extent3d {
+            width: data.width as u32,
+            height: data.height as u32,
+            depth_or_array_layers: 1,
+        };
+
+        // Textures of dmabufs cannot be written to
+        let texture = previous
+            .filter(|texture| {
+                texture.size() == size
+                    && texture.format() == format
+                    && texture.usage().contains(wgpu::TextureUsages::COPY_DST)
+            })
+            .unwrap_or_else(|| {
+                Arc::new(device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Client shm texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                }))
+            });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            contents,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(data.stride as u32),
+                rows_per_image: Some(data.height as u32),
+            },
+            size,
+        );
+
+        Some((texture, opaque))
+    });
+
+    imported.unwrap_or_else(|err| {
+        warn!("Unable to access shm buffer: {err}");
+        None
+    })
+}
+
+fn import_dmabuf(device: &wgpu::Device, buffer: &WlBuffer) -> Option<(Arc<wgpu::Texture>, bool)> {
+    let dmabuf = dmabuf::get_dmabuf(buffer).ok()?;
+    let opaque = matches!(
+        dmabuf.format().code,
+        drm_fourcc::DrmFourcc::Xrgb8888 | drm_fourcc::DrmFourcc::Xbgr8888
+    );
+
+    match unsafe { super::texture::sampled_from_dmabuf(device, dmabuf) } {
+        Ok(texture) => Some((Arc::new(texture), opaque)),
+        Err(err) => {
+            warn!("Unable to import client dmabuf: {err}");
+            None
+        }
+    }
+}
@@ -0,0 +1,38 @@
+struct Uniforms {
+    // Top left corner and size in clip space
+    dst: vec4<f32>,
+    // Top left corner and size in texture coordinates
+    src: vec4<f32>,
+    // Whether the alpha channel of the buffer is padding
+    opaque: f32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var buffer_texture: texture_2d<f32>;
+@group(0) @binding(2) var buffer_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    let corner = vec2<f32>(f32(in_vertex_index % 2), f32(in_vertex_index / 2));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uniforms.dst.xy + corner * uniforms.dst.zw, 0.0, 1.0);
+    out.tex_coords = uniforms.src.xy + corner * uniforms.src.zw;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(buffer_texture, buffer_sampler, in.tex_coords);
+    if uniforms.opaque > 0.5 {
+        color.a = 1.0;
+    }
+    return color;
+}
@@ -1,5 +1,8 @@
 use smithay::backend::allocator::{dmabuf::Dmabuf, Buffer};
-use std::os::fd::IntoRawFd;
+use std::os::{
+    fd::{AsRawFd, BorrowedFd, IntoRawFd},
+    unix::fs::MetadataExt,
+};
 
 /// Equivalent properties for different contexts
 /// https://github.com/gfx-rs/wgpu/blob/trunk/wgpu-hal/src/vulkan/conv.rs
@@ -31,47 +34,150 @@ pub mod properties {
         wgpu::hal::TextureUses::COLOR_TARGET,
         wgpu::TextureUsages::RENDER_ATTACHMENT,
     );
+
+    pub const SAMPLED_USAGE: (
+        ash::vk::ImageUsageFlags,
+        wgpu::hal::TextureUses,
+        wgpu::TextureUsages,
+    ) = (
+        ash::vk::ImageUsageFlags::SAMPLED,
+        wgpu::hal::TextureUses::RESOURCE,
+        wgpu::TextureUsages::TEXTURE_BINDING,
+    );
+
+    /// Texture formats for the supported client buffer formats.
+    /// The alpha channel of the `X` formats has to be ignored when sampling
+    pub fn sampled_format(
+        fourcc: drm_fourcc::DrmFourcc,
+    ) -> Option<(ash::vk::Format, wgpu::TextureFormat)> {
+        use drm_fourcc::DrmFourcc;
+
+        match fourcc {
+            DrmFourcc::Abgr8888 | DrmFourcc::Xbgr8888 => Some(TEXTURE_FORMAT_RGBA),
+            DrmFourcc::Argb8888 | DrmFourcc::Xrgb8888 => Some(TEXTURE_FORMAT_BGRA),
+            _ => None,
+        }
+    }
+
+    const TEXTURE_FORMAT_RGBA: (ash::vk::Format, wgpu::TextureFormat) = (
+        ash::vk::Format::R8G8B8A8_UNORM,
+        wgpu::TextureFormat::Rgba8Unorm,
+    );
+
+    const TEXTURE_FORMAT_BGRA: (ash::vk::Format, wgpu::TextureFormat) = (
+        ash::vk::Format::B8G8R8A8_UNORM,
+        wgpu::TextureFormat::Bgra8Unorm,
+    );
+}
+
+/// Why a dmabuf could not be imported as a texture
+#[derive(Debug)]
+pub enum ImportError {
+    UnsupportedFormat(drm_fourcc::DrmFourcc),
+    NoPlanes,
+    /// The device does not use the Vulkan backend
+    NotVulkan,
+    Fd(std::io::Error),
+    /// No memory type can hold the image
+    NoMemoryType,
+    Vulkan(ash::vk::Result),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => write!(f, "Unsupported format {format:?}"),
+            Self::NoPlanes => write!(f, "The dmabuf has no planes"),
+            Self::NotVulkan => write!(f, "The device does not use Vulkan"),
+            Self::Fd(err) => write!(f, "Unable to access the dmabuf fd: {err}"),
+            Self::NoMemoryType => write!(f, "No memory type can hold the image"),
+            Self::Vulkan(err) => write!(f, "{err}"),
+        }
+    }
 }
 
-// TODO error handling
-pub unsafe fn from_dmabuf(device: &wgpu::Device, dmabuf: &Dmabuf) -> wgpu::Texture {
+impl From<std::io::Error> for ImportError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Fd(err)
+    }
+}
+
+impl From<ash::vk::Result> for ImportError {
+    fn from(err: ash::vk::Result) -> Self {
+        Self::Vulkan(err)
+    }
+}
+
+/// Imports the dmabuf as a texture iced can render into
+pub unsafe fn from_dmabuf(
+    device: &wgpu::Device,
+    dmabuf: &Dmabuf,
+) -> Result<wgpu::Texture, ImportError> {
+    import_dmabuf(
+        device,
+        dmabuf,
+        (properties::TEXTURE_FORMAT.0, properties::TEXTURE_FORMAT.1),
+        properties::USAGE,
+    )
+}
+
+/// Imports a client dmabuf as a texture that can be sampled
+pub unsafe fn sampled_from_dmabuf(
+    device: &wgpu::Device,
+    dmabuf: &Dmabuf,
+) -> Result<wgpu::Texture, ImportError> {
+    let format = properties::sampled_format(dmabuf.format().code)
+        .ok_or(ImportError::UnsupportedFormat(dmabuf.format().code))?;
+    import_dmabuf(device, dmabuf, format, properties::SAMPLED_USAGE)
+}
+
+unsafe fn import_dmabuf(
+    device: &wgpu::Device,
+    dmabuf: &Dmabuf,
+    format: (ash::vk::Format, wgpu::TextureFormat),
+    usage: (
+        ash::vk::ImageUsageFlags,
+        wgpu::hal::TextureUses,
+        wgpu::TextureUsages,
+    ),
+) -> Result<wgpu::Texture, ImportError> {
     let (hal_texture, hal_descriptor) = device
         .as_hal::<wgpu::hal::vulkan::Api, _, _>(|device| {
-            device.map(|device| hal_from_dmabuf(device, dmabuf))
+            device.map(|device| hal_from_dmabuf(device, dmabuf, format, (usage.0, usage.1)))
         })
         .flatten()
-        .expect("Unable to create hal texture");
+        .ok_or(ImportError::NotVulkan)??;
 
-    device.create_texture_from_hal::<wgpu::hal::vulkan::Api>(
+    Ok(device.create_texture_from_hal::<wgpu::hal::vulkan::Api>(
         hal_texture,
         &wgpu::TextureDescriptor {
             label: hal_descriptor.label,
             dimension: hal_descriptor.dimension,
             size: hal_descriptor.size,
             format: hal_descriptor.format,
-            usage: properties::USAGE.2,
+            usage: usage.2,
             mip_level_count: hal_descriptor.mip_level_count,
             sample_count: hal_descriptor.sample_count,
             view_formats: hal_descriptor.view_formats.as_slice(),
         },
-    )
+    ))
 }
 
 unsafe fn hal_from_dmabuf(
     device: &wgpu::hal::vulkan::Device,
     dmabuf: &Dmabuf,
-) -> (
-    wgpu::hal::vulkan::Texture,
-    wgpu::hal::TextureDescriptor<'static>,
-) {
+    format: (ash::vk::Format, wgpu::TextureFormat),
+    usage: (ash::vk::ImageUsageFlags, wgpu::hal::TextureUses),
+) -> Result<
+    (
+        wgpu::hal::vulkan::Texture,
+        wgpu::hal::TextureDescriptor<'static>,
+    ),
+    ImportError,
+> {
     let vk_device = device.raw_device();
 
-    let dma_fd = dmabuf
-        .handles()
-        .last()
-        .expect("No dmabuf fd")
-        .try_clone_to_owned()
-        .expect("Cant clone dmabuf fd");
+    let disjoint = disjoint(dmabuf)?;
 
     let mut external_memory_info = ash::vk::ExternalMemoryImageCreateInfo::default()
         .handle_types(ash::vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
@@ -93,56 +199,48 @@ unsafe fn hal_from_dmabuf(
         .push_next(&mut external_memory_info)
         .push_next(&mut drm_format_info)
         .image_type(properties::TEXTURE_DIMENSION.0)
-        .format(properties::TEXTURE_FORMAT.0)
+        .format(format.0)
         .mip_levels(properties::MIP_LEVEL_COUNT)
         .array_layers(properties::ARRAY_LAYERS)
         .samples(properties::SAMPLE_COUNT.0)
-        .flags(ash::vk::ImageCreateFlags::empty())
+        .flags(if disjoint {
+            ash::vk::ImageCreateFlags::DISJOINT
+        } else {
+            ash::vk::ImageCreateFlags::empty()
+        })
         .extent(ash::vk::Extent3D {
             width: dmabuf.width(),
             height: dmabuf.height(),
             depth: properties::ARRAY_LAYERS,
         })
         .tiling(ash::vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
-        .usage(properties::USAGE.0)
+        .usage(usage.0)
         .sharing_mode(ash::vk::SharingMode::EXCLUSIVE)
         .initial_layout(ash::vk::ImageLayout::UNDEFINED);
 
-    let image = vk_device
-        .create_image(&image_info, None)
-        .expect("Unable to create image");
-
-    let image_requirements = vk_device.get_image_memory_requirements(image);
-
-    let memory_type_index = image_requirements.memory_type_bits.trailing_zeros();
+    let image = vk_device.create_image(&image_info, None)?;
 
-    let mut import_memory_info = ash::vk::ImportMemoryFdInfoKHR::default()
-        .handle_type(ash::vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT)
-        .fd(dma_fd.into_raw_fd());
-
-    let mut dedicated_allocate_info = ash::vk::MemoryDedicatedAllocateInfo::default().image(image);
-
-    let allocate_info = ash::vk::MemoryAllocateInfo::default()
-        .push_next(&mut import_memory_info)
-        .push_next(&mut dedicated_allocate_info)
-        .allocation_size(image_requirements.size)
-        .memory_type_index(memory_type_index);
-
-    let memory = vk_device
-        .allocate_memory(&allocate_info, None)
-        .expect("Unable to allocate memory");
-
-    vk_device
-        .bind_image_memory(image, memory, 0)
-        .expect("Unable to bind image memory");
+    let mut memories = Vec::new();
+    let bound = if disjoint {
+        bind_plane_memories(vk_device, image, dmabuf, &mut memories)
+    } else {
+        bind_memory(vk_device, image, dmabuf, &mut memories)
+    };
+    if let Err(err) = bound {
+        for memory in memories {
+            vk_device.free_memory(memory, None);
+        }
+        vk_device.destroy_image(image, None);
+        return Err(err);
+    }
 
     let texture_descriptor = wgpu::hal::TextureDescriptor {
         label: Some("Iced dmabuf imported texture"),
         dimension: properties::TEXTURE_DIMENSION.1,
         mip_level_count: properties::MIP_LEVEL_COUNT,
         sample_count: properties::SAMPLE_COUNT.1,
-        format: properties::TEXTURE_FORMAT.1,
-        usage: properties::USAGE.1,
+        format: format.1,
+        usage: usage.1,
         size: wgpu::Extent3d {
             width: dmabuf.width(),
             height: dmabuf.height(),
@@ -160,7 +258,9 @@ unsafe fn hal_from_dmabuf(
         let vk_free_memory = vk_device.fp_v1_0().free_memory;
         move || {
             vk_destroy_image(vk_device_handle, image, std::ptr::null());
-            vk_free_memory(vk_device_handle, memory, std::ptr::null());
+            for memory in memories {
+                vk_free_memory(vk_device_handle, memory, std::ptr::null());
+            }
         }
     };
 
@@ -170,5 +270,125 @@ unsafe fn hal_from_dmabuf(
         Some(Box::new(drop_callback)),
     );
 
-    (hal_texture, texture_descriptor)
+    Ok((hal_texture, texture_descriptor))
+}
+
+/// Whether the planes of the dmabuf are in separate buffers, which need their own memory
+fn disjoint(dmabuf: &Dmabuf) -> Result<bool, ImportError> {
+    let mut buffers = Vec::new();
+    for fd in dmabuf.handles() {
+        let metadata = std::fs::File::from(fd.try_clone_to_owned()?).metadata()?;
+        buffers.push((metadata.dev(), metadata.ino()));
+    }
+
+    if buffers.is_empty() {
+        return Err(ImportError::NoPlanes);
+    }
+    Ok(buffers.windows(2).any(|pair| pair[0] != pair[1]))
+}
+
+/// Binds the buffer all planes are in to the image.
+/// The memory is added to the memories, even if binding fails
+unsafe fn bind_memory(
+    vk_device: &ash::Device,
+    image: ash::vk::Image,
+    dmabuf: &Dmabuf,
+    memories: &mut Vec<ash::vk::DeviceMemory>,
+) -> Result<(), ImportError> {
+    let fd = dmabuf.handles().next().ok_or(ImportError::NoPlanes)?;
+    let requirements = vk_device.get_image_memory_requirements(image);
+
+    let memory = import_memory(vk_device, fd, requirements, Some(image))?;
+    memories.push(memory);
+
+    vk_device.bind_image_memory(image, memory, 0)?;
+    Ok(())
+}
+
+/// Binds the buffer of each plane to the plane of the disjoint image.
+/// The memory is added to the memories, even if binding fails
+unsafe fn bind_plane_memories(
+    vk_device: &ash::Device,
+    image: ash::vk::Image,
+    dmabuf: &Dmabuf,
+    memories: &mut Vec<ash::vk::DeviceMemory>,
+) -> Result<(), ImportError> {
+    let aspects = [
+        ash::vk::ImageAspectFlags::MEMORY_PLANE_0_EXT,
+        ash::vk::ImageAspectFlags::MEMORY_PLANE_1_EXT,
+        ash::vk::ImageAspectFlags::MEMORY_PLANE_2_EXT,
+        ash::vk::ImageAspectFlags::MEMORY_PLANE_3_EXT,
+    ];
+
+    for (fd, aspect) in dmabuf.handles().zip(aspects) {
+        let mut plane_info =
+            ash::vk::ImagePlaneMemoryRequirementsInfo::default().plane_aspect(aspect);
+        let requirements_info = ash::vk::ImageMemoryRequirementsInfo2::default()
+            .image(image)
+            .push_next(&mut plane_info);
+        let mut requirements = ash::vk::MemoryRequirements2::default();
+        vk_device.get_image_memory_requirements2(&requirements_info, &mut requirements);
+
+        memories.push(import_memory(
+            vk_device,
+            fd,
+            requirements.memory_requirements,
+            None,
+        )?);
+    }
+
+    let mut plane_infos: Vec<_> = aspects
+        .into_iter()
+        .take(memories.len())
+        .map(|aspect| ash::vk::BindImagePlaneMemoryInfo::default().plane_aspect(aspect))
+        .collect();
+    let bind_infos: Vec<_> = memories
+        .iter()
+        .zip(&mut plane_infos)
+        .map(|(memory, plane_info)| {
+            ash::vk::BindImageMemoryInfo::default()
+                .image(image)
+                .memory(*memory)
+                .push_next(plane_info)
+        })
+        .collect();
+
+    vk_device.bind_image_memory2(&bind_infos)?;
+    Ok(())
+}
+
+/// Imports the buffer of the fd as memory.
+/// Memory imported for a whole image is dedicated to it
+unsafe fn import_memory(
+    vk_device: &ash::Device,
+    fd: BorrowedFd<'_>,
+    requirements: ash::vk::MemoryRequirements,
+    dedicated_image: Option<ash::vk::Image>,
+) -> Result<ash::vk::DeviceMemory, ImportError> {
+    if requirements.memory_type_bits == 0 {
+        return Err(ImportError::NoMemoryType);
+    }
+
+    let fd = fd.try_clone_to_owned()?;
+
+    let mut import_memory_info = ash::vk::ImportMemoryFdInfoKHR::default()
+        .handle_type(ash::vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT)
+        .fd(fd.as_raw_fd());
+
+    let mut dedicated_allocate_info = ash::vk::MemoryDedicatedAllocateInfo::default();
+
+    let mut allocate_info = ash::vk::MemoryAllocateInfo::default()
+        .push_next(&mut import_memory_info)
+        .allocation_size(requirements.size)
+        .memory_type_index(requirements.memory_type_bits.trailing_zeros());
+    if let Some(image) = dedicated_image {
+        dedicated_allocate_info = dedicated_allocate_info.image(image);
+        allocate_info = allocate_info.push_next(&mut dedicated_allocate_info);
+    }
+
+    let memory = vk_device.allocate_memory(&allocate_info, None)?;
+    // The memory owns the fd once it was imported successfully
+    let _ = fd.into_raw_fd();
+
+    Ok(memory)
 }
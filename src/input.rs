@@ -1,9 +1,22 @@
-use smithay::utils::SERIAL_COUNTER;
-use smithay_input::{Event as _, KeyboardKeyEvent as _};
+use crate::keybindings::KeyChord;
+use smithay::{
+    desktop::{self, WindowSurfaceType},
+    input::pointer::{AxisFrame, ButtonEvent, MotionEvent, PointerHandle},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, SERIAL_COUNTER},
+    wayland::shell::wlr_layer::Layer,
+};
+use smithay_input::{
+    AbsolutePositionEvent as _, Event as _, KeyboardKeyEvent as _, PointerAxisEvent as _,
+    PointerButtonEvent as _, PointerMotionEvent as _,
+};
 
 mod smithay_input {
     pub use smithay::{
-        backend::input::{Event, InputBackend, InputEvent, KeyboardKeyEvent},
+        backend::input::{
+            AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
+            KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
+        },
         input::{keyboard::FilterResult, SeatHandler},
     };
 }
@@ -20,19 +33,126 @@ pub enum ExtraInputEvent {}
 
 impl<B: crate::Backend> crate::App<B> {
     pub fn process_input<IB: smithay_input::InputBackend>(&mut self, event: InputEvent<IB>) {
+        // The pointer and the shell may look different afterwards
+        self.common.comp.schedule_redraw();
+
         match event {
             InputEvent::Basic(b_event) => match b_event {
                 smithay_input::InputEvent::Keyboard { event: k_event } => {
-                    if let Some(keyboard) = self.common.comp.seat.get_keyboard() {
-                        keyboard.input(
-                            self,
-                            k_event.key_code(),
-                            k_event.state(),
-                            SERIAL_COUNTER.next_serial(),
-                            k_event.time_msec(),
-                            |_, _, _| smithay_input::FilterResult::Forward::<()>,
+                    let Some(keyboard) = self.common.comp.seat.get_keyboard() else {
+                        return;
+                    };
+
+                    let key_state = k_event.state();
+                    // Without a focused client the keys belong to the shell
+                    let shell_focused = keyboard.current_focus().is_none();
+                    let action = keyboard.input(
+                        self,
+                        k_event.key_code(),
+                        key_state,
+                        SERIAL_COUNTER.next_serial(),
+                        k_event.time_msec(),
+                        |app, modifiers, handle| {
+                            let keybindings = &mut app.common.comp.keybindings;
+                            let intercepted = match key_state {
+                                smithay_input::KeyState::Pressed => handle
+                                    .raw_syms()
+                                    .first()
+                                    .and_then(|keysym| {
+                                        keybindings.press(
+                                            handle.raw_code(),
+                                            KeyChord::new(modifiers.into(), *keysym),
+                                        )
+                                    })
+                                    .map(Some),
+                                // Releases of intercepted keys are swallowed as
+                                // the client never saw the matching press
+                                smithay_input::KeyState::Released => {
+                                    keybindings.release(handle.raw_code()).then_some(None)
+                                }
+                            };
+
+                            match intercepted {
+                                Some(action) => smithay_input::FilterResult::Intercept(action),
+                                None => {
+                                    if shell_focused {
+                                        app.common.shell_driver.schedule_event(
+                                            crate::iced::conversion::keyboard_event(
+                                                key_state, modifiers, &handle,
+                                            ),
+                                        );
+                                    }
+                                    smithay_input::FilterResult::Forward
+                                }
+                            }
+                        },
+                    );
+
+                    if let Some(Some(action)) = action {
+                        self.run_action(action);
+                    }
+                }
+                smithay_input::InputEvent::PointerMotion { event: m_event } => {
+                    let location = self.common.comp.pointer_location + m_event.delta();
+                    self.pointer_motion(location, m_event.time_msec());
+                }
+                smithay_input::InputEvent::PointerMotionAbsolute { event: m_event } => {
+                    let space = &self.common.comp.space;
+                    let Some(output_geometry) = space
+                        .outputs()
+                        .next()
+                        .and_then(|output| space.output_geometry(output))
+                    else {
+                        return;
+                    };
+
+                    let location = m_event.position_transformed(output_geometry.size)
+                        + output_geometry.loc.to_f64();
+                    self.pointer_motion(location, m_event.time_msec());
+                }
+                smithay_input::InputEvent::PointerButton { event: b_event } => {
+                    self.pointer_button(
+                        b_event.button_code(),
+                        b_event.state(),
+                        b_event.time_msec(),
+                    );
+                }
+                smithay_input::InputEvent::PointerAxis { event: a_event } => {
+                    let Some(pointer) = self.common.comp.seat.get_pointer() else {
+                        return;
+                    };
+
+                    if self.pointer_on_shell(&pointer) {
+                        self.common.shell_driver.schedule_event(
+                            crate::iced::conversion::scroll_event::<IB, _>(&a_event),
                         );
+                        return;
+                    }
+
+                    let mut frame = AxisFrame::new(a_event.time_msec()).source(a_event.source());
+
+                    for axis in [
+                        smithay_input::Axis::Horizontal,
+                        smithay_input::Axis::Vertical,
+                    ] {
+                        // Wheel events without continuous values are converted
+                        // using the usual 15 degrees per notch
+                        let amount = a_event.amount(axis).unwrap_or_else(|| {
+                            a_event.amount_v120(axis).unwrap_or(0.0) * 15.0 / 120.0
+                        });
+
+                        if amount != 0.0 {
+                            frame = frame.value(axis, amount);
+                            if let Some(discrete) = a_event.amount_v120(axis) {
+                                frame = frame.v120(axis, discrete as i32);
+                            }
+                        } else if a_event.source() == smithay_input::AxisSource::Finger {
+                            frame = frame.stop(axis);
+                        }
                     }
+
+                    pointer.axis(self, frame);
+                    pointer.frame(self);
                 }
                 _ => {}
             },
@@ -41,8 +161,267 @@ impl<B: crate::Backend> crate::App<B> {
     }
 
     pub fn set_focus(&mut self, focus: <Self as smithay_input::SeatHandler>::KeyboardFocus) {
+        // Exclusive layer surfaces keep the focus until they are gone
+        let focus = self.exclusive_layer().unwrap_or(focus);
+
         if let Some(keyboard) = self.common.comp.seat.get_keyboard() {
             keyboard.set_focus(self, Some(focus), SERIAL_COUNTER.next_serial());
         }
     }
+
+    /// Gives keyboard focus to the toplevel of the window.
+    /// Floating windows are brought to the top
+    pub fn focus_window(&mut self, window: &desktop::Window) {
+        if let Some(workspace) = self.common.comp.workspaces.containing_mut(window) {
+            let floating = &mut workspace.floating;
+            if let Some(index) = floating
+                .iter()
+                .position(|floating| &floating.window == window)
+            {
+                let focused = floating.remove(index);
+                floating.push(focused);
+            }
+        }
+
+        if let Some(surface) = crate::util::surface::window_surface(window) {
+            self.set_focus(surface);
+        }
+    }
+
+    /// Returns the window owning the surface with keyboard focus
+    pub fn focused_window(&self) -> Option<desktop::Window> {
+        let focus = self.common.comp.seat.get_keyboard()?.current_focus()?;
+        crate::util::surface::find_window(
+            &crate::util::surface::get_root_surface(&focus),
+            &self.common.comp.windows,
+        )
+    }
+
+    /// Returns the surface under the given location and the location of that surface
+    pub fn surface_under(
+        &self,
+        location: Point<f64, Logical>,
+    ) -> Option<(WlSurface, Point<f64, Logical>)> {
+        let layer_surface_under = |layers: &[Layer]| {
+            let (layer, layer_location) = self.layer_under(layers, location)?;
+            layer
+                .surface_under(location - layer_location.to_f64(), WindowSurfaceType::ALL)
+                .map(|(surface, surface_location)| {
+                    (surface, (surface_location + layer_location).to_f64())
+                })
+        };
+
+        // Menus and tooltips of X11 clients are above everything
+        let override_redirect_under = || {
+            self.common
+                .comp
+                .override_redirect
+                .iter()
+                .rev()
+                .find_map(|window| {
+                    desktop::utils::under_from_surface_tree(
+                        &window.wl_surface()?,
+                        location,
+                        window.geometry().loc,
+                        WindowSurfaceType::ALL,
+                    )
+                })
+                .map(|(surface, surface_location)| (surface, surface_location.to_f64()))
+        };
+
+        let window_surface_under = || {
+            let comp = &self.common.comp;
+            // Title bars of floating windows cover the tiled windows below
+            let floating = comp.workspace().floating.iter().rev().find(|floating| {
+                floating.geometry.to_f64().contains(location)
+                    && comp.space.element_location(&floating.window).is_some()
+            });
+            let (window, window_location) = match floating {
+                Some(floating) => (
+                    &floating.window,
+                    comp.space.element_location(&floating.window)?,
+                ),
+                None => comp.space.element_under(location)?,
+            };
+
+            window
+                .surface_under(location - window_location.to_f64(), WindowSurfaceType::ALL)
+                .map(|(surface, surface_location)| {
+                    (surface, (surface_location + window_location).to_f64())
+                })
+        };
+
+        // Lower layers are hidden by the shell, except in exclusive zones
+        let on_shell = self
+            .common
+            .comp
+            .shell_geometry()
+            .is_some_and(|geometry| geometry.to_f64().contains(location));
+
+        // Fullscreen windows cover everything but overlays
+        if self.common.comp.workspace().fullscreen.is_some() {
+            return override_redirect_under()
+                .or_else(|| layer_surface_under(&[Layer::Overlay]))
+                .or_else(window_surface_under);
+        }
+
+        override_redirect_under()
+            .or_else(|| layer_surface_under(&[Layer::Overlay, Layer::Top]))
+            .or_else(window_surface_under)
+            .or_else(|| {
+                (!on_shell)
+                    .then(|| layer_surface_under(&[Layer::Bottom, Layer::Background]))
+                    .flatten()
+            })
+    }
+
+    fn pointer_motion(&mut self, location: Point<f64, Logical>, time: u32) {
+        let Some(pointer) = self.common.comp.seat.get_pointer() else {
+            return;
+        };
+
+        let location = self.clamp_to_outputs(location);
+        self.common.comp.pointer_location = location;
+
+        let under = self.surface_under(location);
+        self.move_shell_cursor(location, under.is_some());
+
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location,
+                serial: SERIAL_COUNTER.next_serial(),
+                time,
+            },
+        );
+        pointer.frame(self);
+    }
+
+    fn pointer_button(&mut self, button: u32, state: smithay_input::ButtonState, time: u32) {
+        let Some(pointer) = self.common.comp.seat.get_pointer() else {
+            return;
+        };
+
+        let serial = SERIAL_COUNTER.next_serial();
+
+        // The button may be released elsewhere than where it was pressed
+        if state == smithay_input::ButtonState::Released {
+            self.common
+                .comp
+                .shell_buttons
+                .retain(|pressed| *pressed != button);
+        }
+
+        if self.pointer_on_shell(&pointer) {
+            // Clicking the shell takes the keyboard away from clients
+            if state == smithay_input::ButtonState::Pressed && self.exclusive_layer().is_none() {
+                if let Some(keyboard) = self.common.comp.seat.get_keyboard() {
+                    keyboard.set_focus(self, None, serial);
+                }
+            }
+
+            if state == smithay_input::ButtonState::Pressed {
+                self.common.comp.shell_buttons.push(button);
+            }
+
+            self.common
+                .shell_driver
+                .schedule_event(crate::iced::conversion::mouse_button_event(button, state));
+            return;
+        }
+
+        // Click to focus, unless a grab like a popup is active
+        if state == smithay_input::ButtonState::Pressed && !pointer.is_grabbed() {
+            let location = self.common.comp.pointer_location;
+            let upper_layers: &[Layer] = if self.common.comp.workspace().fullscreen.is_some() {
+                &[Layer::Overlay]
+            } else {
+                &[Layer::Overlay, Layer::Top]
+            };
+
+            if let Some((layer, _)) = self.layer_under(upper_layers, location) {
+                // Layer surfaces without keyboard interactivity never get the focus
+                if layer.can_receive_keyboard_focus() {
+                    self.set_focus(layer.wl_surface().clone());
+                }
+            } else if let Some(window) = self
+                .common
+                .comp
+                .space
+                .element_under(location)
+                .map(|(window, _)| window.clone())
+            {
+                self.focus_window(&window);
+            } else if let Some((layer, _)) =
+                self.layer_under(&[Layer::Bottom, Layer::Background], location)
+            {
+                if layer.can_receive_keyboard_focus() {
+                    self.set_focus(layer.wl_surface().clone());
+                }
+            }
+        }
+
+        pointer.button(
+            self,
+            &ButtonEvent {
+                serial,
+                time,
+                button,
+                state,
+            },
+        );
+        pointer.frame(self);
+    }
+
+    /// Whether pointer input should go to the shell instead of clients
+    fn pointer_on_shell(&self, pointer: &PointerHandle<Self>) -> bool {
+        !pointer.is_grabbed() && self.common.shell_driver.cursor().position().is_some()
+    }
+
+    /// Moves the shell cursor, which is only available while no client surface is under the pointer
+    fn move_shell_cursor(&mut self, location: Point<f64, Logical>, over_client: bool) {
+        let position = self
+            .common
+            .comp
+            .shell_geometry()
+            .filter(|geometry| !over_client && geometry.to_f64().contains(location))
+            .map(|geometry| location - geometry.loc.to_f64())
+            .map(|position| iced_core::Point::new(position.x as f32, position.y as f32));
+
+        let driver = &mut self.common.shell_driver;
+        let event = match position {
+            Some(position) => iced_core::mouse::Event::CursorMoved { position },
+            None if driver.cursor().position().is_some() => iced_core::mouse::Event::CursorLeft,
+            None => return,
+        };
+
+        driver.set_cursor(position.map_or(
+            iced_core::mouse::Cursor::Unavailable,
+            iced_core::mouse::Cursor::Available,
+        ));
+        driver.schedule_event(iced_core::Event::Mouse(event));
+    }
+
+    /// Keeps the location inside the area covered by outputs
+    fn clamp_to_outputs(&self, location: Point<f64, Logical>) -> Point<f64, Logical> {
+        let space = &self.common.comp.space;
+        let Some(area) = space
+            .outputs()
+            .filter_map(|output| space.output_geometry(output))
+            .reduce(|area, geometry| area.merge(geometry))
+        else {
+            return location;
+        };
+
+        (
+            location
+                .x
+                .clamp(area.loc.x as f64, (area.loc.x + area.size.w - 1) as f64),
+            location
+                .y
+                .clamp(area.loc.y as f64, (area.loc.y + area.size.h - 1) as f64),
+        )
+            .into()
+    }
 }
@@ -0,0 +1,475 @@
+use crate::action::{Action, Target};
+use protocol::{
+    Event, OutputInfo, Reply, ReplyMessage, Request, RequestMessage, Response, WindowInfo,
+    WorkspaceInfo, VERSION,
+};
+use smithay::{
+    desktop,
+    reexports::{
+        calloop::{self, generic::Generic},
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tracing::{error, info, warn};
+
+pub mod protocol;
+
+static NEXT_WINDOW_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Connections whose request grows larger without a line break are closed
+const MAX_REQUEST_LEN: usize = 64 * 1024;
+/// Connections that do not read their replies and events while more are queued are closed
+const MAX_QUEUED_LEN: usize = 1024 * 1024;
+
+/// Identifier of a window for IPC clients, stored in the user data of the window
+struct WindowId(u64);
+
+/// Returns the id IPC clients know the window by
+pub fn window_id(window: &desktop::Window) -> u64 {
+    window
+        .user_data()
+        .get_or_insert_threadsafe(|| WindowId(NEXT_WINDOW_ID.fetch_add(1, Ordering::Relaxed)))
+        .0
+}
+
+/// Location of the IPC socket, next to the wayland socket
+pub fn socket_path() -> Option<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")?;
+    let display = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
+    Some(PathBuf::from(runtime_dir).join(format!("chadland-{display}.sock")))
+}
+
+/// An IPC connection and the bytes that could not be written to it yet
+struct Connection {
+    stream: UnixStream,
+    outgoing: Vec<u8>,
+    /// Receives [Event]s
+    subscribed: bool,
+}
+
+impl Connection {
+    /// Queues the bytes and writes as many of the queued ones as the socket accepts
+    fn send(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.outgoing.extend_from_slice(bytes);
+        self.flush()
+    }
+
+    /// Writes the queued bytes until the socket would block.
+    /// The rest is written once the connection is writable again
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut written = 0;
+        let result = loop {
+            if written == self.outgoing.len() {
+                break Ok(());
+            }
+            match (&self.stream).write(&self.outgoing[written..]) {
+                Ok(0) => break Err(std::io::ErrorKind::WriteZero.into()),
+                Ok(count) => written += count,
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => break Err(err),
+            }
+        };
+        self.outgoing.drain(..written);
+
+        if result.is_ok() && self.outgoing.len() > MAX_QUEUED_LEN {
+            return Err(std::io::Error::other(
+                "the client does not keep up with reading",
+            ));
+        }
+        result
+    }
+
+    /// Makes the source of the connection remove itself, as it owns another handle of the socket
+    fn close(&self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// State of the IPC socket shared by all connections
+#[derive(Default)]
+pub struct Ipc {
+    path: Option<PathBuf>,
+    /// Open connections by their id
+    connections: HashMap<u64, Connection>,
+    next_connection: u64,
+    /// Id of the last window reported as focused
+    focused: Option<u64>,
+}
+
+impl Ipc {
+    /// Sends the event to every subscribed connection.
+    /// Connections that are closed or do not keep up with reading are dropped
+    pub fn emit(&mut self, event: Event) {
+        if !self
+            .connections
+            .values()
+            .any(|connection| connection.subscribed)
+        {
+            return;
+        }
+
+        let line = encode(Reply::Event(event));
+        self.connections.retain(|_, connection| {
+            if !connection.subscribed {
+                return true;
+            }
+            match connection.send(&line) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!("Closing IPC connection after write error: {err}");
+                    connection.close();
+                    false
+                }
+            }
+        });
+    }
+
+    /// Sends the reply to the connection, closing it if that fails
+    fn reply(&mut self, connection_id: u64, response: Response) -> bool {
+        let Some(connection) = self.connections.get_mut(&connection_id) else {
+            return false;
+        };
+        if let Err(err) = connection.send(&encode(Reply::Response(response))) {
+            warn!("Closing IPC connection after write error: {err}");
+            connection.close();
+            self.connections.remove(&connection_id);
+            return false;
+        }
+        true
+    }
+
+    /// Emits a focus event if the focused window changed since the last one
+    pub fn focus_changed(&mut self, window: Option<WindowInfo>) {
+        let id = window.as_ref().map(|window| window.id);
+        if self.focused != id {
+            self.focused = id;
+            self.emit(Event::WindowFocused { window });
+        }
+    }
+}
+
+impl Drop for Ipc {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Serializes the reply as a single line
+fn encode(reply: Reply) -> Vec<u8> {
+    let mut line = serde_json::to_vec(&ReplyMessage {
+        version: VERSION,
+        reply,
+    })
+    .expect("Unable to serialize IPC reply");
+    line.push(b'\n');
+    line
+}
+
+/// Create and initialize the IPC socket
+pub fn init_ipc<B: crate::Backend>(app: &mut crate::App<B>) {
+    let Some(path) = socket_path() else {
+        warn!("XDG_RUNTIME_DIR is not set, the IPC socket is disabled");
+        return;
+    };
+
+    // A crashed instance may have left its socket behind
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Unable to create IPC socket {path:?}: {err}");
+            return;
+        }
+    };
+    listener
+        .set_nonblocking(true)
+        .expect("Unable to make IPC socket non-blocking");
+
+    info!("Listening for IPC connections on {path:?}");
+    // Processes spawned by the compositor should control this instance
+    std::env::set_var(protocol::SOCKET_ENV, &path);
+    app.common.comp.ipc.path = Some(path);
+
+    app.common
+        .comp
+        .loop_handle
+        .insert_source(
+            Generic::new(listener, calloop::Interest::READ, calloop::Mode::Level),
+            |_, listener, app| {
+                loop {
+                    match listener.accept() {
+                        Ok((stream, _)) => app.add_ipc_connection(stream),
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+                        Err(err) => {
+                            error!("Unable to accept IPC connection: {err}");
+                            break;
+                        }
+                    }
+                }
+                Ok(calloop::PostAction::Continue)
+            },
+        )
+        .expect("Unable to insert IPC socket source");
+}
+
+impl<B: crate::Backend> crate::App<B> {
+    /// Answers the requests of the connection, one JSON message per line
+    fn add_ipc_connection(&mut self, stream: UnixStream) {
+        if let Err(err) = stream.set_nonblocking(true) {
+            error!("Unable to make IPC connection non-blocking: {err}");
+            return;
+        }
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(err) => {
+                error!("Unable to clone IPC connection: {err}");
+                return;
+            }
+        };
+
+        let ipc = &mut self.common.comp.ipc;
+        let id = ipc.next_connection;
+        ipc.next_connection += 1;
+        ipc.connections.insert(
+            id,
+            Connection {
+                stream: writer,
+                outgoing: Vec::new(),
+                subscribed: false,
+            },
+        );
+
+        let mut buffer = Vec::new();
+        // Edge triggered, so the writable event only arrives once queued bytes can be written
+        let source = Generic::new(stream, calloop::Interest::BOTH, calloop::Mode::Edge);
+        let result =
+            self.common
+                .comp
+                .loop_handle
+                .insert_source(source, move |readiness, stream, app| {
+                    Ok(app.ipc_connection_ready(id, readiness, stream, &mut buffer))
+                });
+
+        if let Err(err) = result {
+            error!("Unable to insert IPC connection source: {}", err.error);
+            self.common.comp.ipc.connections.remove(&id);
+        }
+    }
+
+    /// Answers the complete requests and writes the queued bytes
+    fn ipc_connection_ready(
+        &mut self,
+        id: u64,
+        readiness: calloop::Readiness,
+        mut stream: &UnixStream,
+        buffer: &mut Vec<u8>,
+    ) -> calloop::PostAction {
+        let mut chunk = [0; 4096];
+        while readiness.readable {
+            match stream.read(&mut chunk) {
+                Ok(0) => return self.close_ipc_connection(id),
+                Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    warn!("Closing IPC connection after read error: {err}");
+                    return self.close_ipc_connection(id);
+                }
+            }
+
+            while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let response = self.handle_ipc_request(&line, id);
+                if !self.common.comp.ipc.reply(id, response) {
+                    return self.close_ipc_connection(id);
+                }
+            }
+            if buffer.len() > MAX_REQUEST_LEN {
+                warn!("Closing IPC connection after an overlong request");
+                return self.close_ipc_connection(id);
+            }
+        }
+
+        if readiness.writable {
+            let Some(connection) = self.common.comp.ipc.connections.get_mut(&id) else {
+                return calloop::PostAction::Remove;
+            };
+            if let Err(err) = connection.flush() {
+                warn!("Closing IPC connection after write error: {err}");
+                return self.close_ipc_connection(id);
+            }
+        }
+
+        calloop::PostAction::Continue
+    }
+
+    /// Forgets the connection, its source is removed by returning the action
+    fn close_ipc_connection(&mut self, id: u64) -> calloop::PostAction {
+        self.common.comp.ipc.connections.remove(&id);
+        calloop::PostAction::Remove
+    }
+
+    fn handle_ipc_request(&mut self, line: &[u8], connection_id: u64) -> Response {
+        let error = |message: String| Response::Error { message };
+
+        // The version is checked first, so requests of other versions are not reported as malformed
+        let value: serde_json::Value = match serde_json::from_slice(line) {
+            Ok(value) => value,
+            Err(err) => return error(format!("Invalid JSON: {err}")),
+        };
+        match value.get("version").and_then(serde_json::Value::as_u64) {
+            Some(version) if version == VERSION as u64 => {}
+            Some(version) => {
+                return error(format!(
+                    "Unsupported protocol version {version}, expected {VERSION}"
+                ))
+            }
+            None => return error("Missing protocol version".to_string()),
+        }
+        let message: RequestMessage = match serde_json::from_value(value) {
+            Ok(message) => message,
+            Err(err) => return error(format!("Invalid request: {err}")),
+        };
+
+        let focused = self.focused_window();
+        let comp = &self.common.comp;
+        match message.request {
+            Request::Windows => Response::Windows {
+                windows: comp
+                    .windows
+                    .iter()
+                    .map(|window| self.window_info(window, focused.as_ref() == Some(window)))
+                    .collect(),
+            },
+            Request::Outputs => Response::Outputs {
+                outputs: comp
+                    .space
+                    .outputs()
+                    .map(|output| {
+                        let properties = output.physical_properties();
+                        OutputInfo {
+                            name: output.name(),
+                            make: properties.make,
+                            model: properties.model,
+                            geometry: comp.space.output_geometry(output).map(Into::into),
+                            refresh: output.current_mode().map(|mode| mode.refresh),
+                            scale: output.current_scale().fractional_scale(),
+                            workspace: comp.workspaces.shown_on(Some(output)),
+                        }
+                    })
+                    .collect(),
+            },
+            Request::Workspaces => Response::Workspaces {
+                workspaces: comp
+                    .workspaces
+                    .iter()
+                    .map(|(number, workspace)| WorkspaceInfo {
+                        number,
+                        output: comp
+                            .workspaces
+                            .output_of(number)
+                            .map(|output| output.name()),
+                        layout: workspace.layout.kind.layout().name().to_string(),
+                        windows: workspace.windows.iter().map(window_id).collect(),
+                    })
+                    .collect(),
+            },
+            Request::Focused => Response::Focused {
+                window: focused.map(|window| self.window_info(&window, true)),
+            },
+            Request::Action { action } => match self.ipc_action(action) {
+                Ok(action) => {
+                    self.run_action(action);
+                    Response::Done
+                }
+                Err(message) => error(message),
+            },
+            Request::Subscribe => {
+                if let Some(connection) = self.common.comp.ipc.connections.get_mut(&connection_id) {
+                    connection.subscribed = true;
+                }
+                Response::Subscribed
+            }
+        }
+    }
+
+    /// Describes the window for IPC clients
+    pub fn window_info(&self, window: &desktop::Window, focused: bool) -> WindowInfo {
+        let comp = &self.common.comp;
+        let (title, app_id) = crate::util::surface::title_and_app_id(window);
+        let workspace = comp.workspaces.containing(window);
+
+        WindowInfo {
+            id: window_id(window),
+            title,
+            app_id,
+            workspace: comp.workspaces.number_of(window),
+            focused,
+            floating: comp.floating(window).is_some(),
+            fullscreen: workspace
+                .is_some_and(|workspace| workspace.fullscreen.as_ref() == Some(window)),
+            maximized: workspace
+                .is_some_and(|workspace| workspace.maximized.as_ref() == Some(window)),
+            minimized: workspace.is_some_and(|workspace| workspace.minimized.contains(window)),
+            geometry: comp.space.element_geometry(window).map(Into::into),
+        }
+    }
+
+    fn ipc_action(&self, action: protocol::Action) -> Result<Action, String> {
+        use protocol::Action as IpcAction;
+
+        Ok(match action {
+            IpcAction::Spawn { command } => Action::Spawn(command),
+            IpcAction::Focus { window } => Action::Focus(self.ipc_window(window)?),
+            IpcAction::FocusNext => Action::FocusNext,
+            IpcAction::FocusPrevious => Action::FocusPrevious,
+            IpcAction::Close { window } => Action::Close(self.ipc_target(window)?),
+            IpcAction::ToggleFloating { window } => {
+                Action::ToggleFloating(self.ipc_target(window)?)
+            }
+            IpcAction::ToggleFullscreen { window } => {
+                Action::ToggleFullscreen(self.ipc_target(window)?)
+            }
+            IpcAction::ToggleMaximize { window } => {
+                Action::ToggleMaximize(self.ipc_target(window)?)
+            }
+            IpcAction::Minimize { window } => Action::Minimize(self.ipc_target(window)?),
+            IpcAction::SwitchWorkspace { workspace } => Action::SwitchWorkspace(workspace),
+            IpcAction::Move { window, workspace } => {
+                Action::MoveToWorkspace(self.ipc_target(window)?, workspace)
+            }
+            IpcAction::NextLayout => Action::NextLayout,
+            IpcAction::ReloadConfig => Action::ReloadConfig,
+            IpcAction::Quit => Action::Quit,
+        })
+    }
+
+    fn ipc_target(&self, window: protocol::WindowTarget) -> Result<Target, String> {
+        match window {
+            Some(id) => self.ipc_window(id).map(Target::Window),
+            None => Ok(Target::Focused),
+        }
+    }
+
+    /// Returns the toplevel surface of the window with the id
+    fn ipc_window(&self, id: u64) -> Result<WlSurface, String> {
+        self.common
+            .comp
+            .windows
+            .iter()
+            .find(|window| window_id(window) == id)
+            .and_then(crate::util::surface::window_surface)
+            .ok_or_else(|| format!("No window with id {id}"))
+    }
+}
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+
+/// Version of the protocol. Requests with a different version are rejected
+pub const VERSION: u32 = 1;
+/// Environment variable with the path of the IPC socket, set for processes spawned by the compositor
+pub const SOCKET_ENV: &str = "CHADLAND_SOCKET";
+
+/// A single line sent by a client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestMessage {
+    pub version: u32,
+    pub request: Request,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Windows,
+    Outputs,
+    Workspaces,
+    /// The window with keyboard focus
+    Focused,
+    Action {
+        action: Action,
+    },
+    /// Turns the connection into a stream of [Event]s after the [Response::Subscribed] reply
+    Subscribe,
+}
+
+/// Window an [Action] applies to. Without an id it is the focused window
+pub type WindowTarget = Option<u64>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Runs a shell command
+    Spawn {
+        command: String,
+    },
+    Focus {
+        window: u64,
+    },
+    FocusNext,
+    FocusPrevious,
+    Close {
+        window: WindowTarget,
+    },
+    ToggleFloating {
+        window: WindowTarget,
+    },
+    ToggleFullscreen {
+        window: WindowTarget,
+    },
+    ToggleMaximize {
+        window: WindowTarget,
+    },
+    Minimize {
+        window: WindowTarget,
+    },
+    SwitchWorkspace {
+        workspace: usize,
+    },
+    /// Moves the window to the workspace
+    Move {
+        window: WindowTarget,
+        workspace: usize,
+    },
+    NextLayout,
+    ReloadConfig,
+    Quit,
+}
+
+/// A single line sent by the compositor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplyMessage {
+    pub version: u32,
+    #[serde(flatten)]
+    pub reply: Reply,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    /// Answer to a request, in the order the requests were sent
+    Response(Response),
+    /// Sent to subscribed connections whenever something happens
+    Event(Event),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Windows {
+        windows: Vec<WindowInfo>,
+    },
+    Outputs {
+        outputs: Vec<OutputInfo>,
+    },
+    Workspaces {
+        workspaces: Vec<WorkspaceInfo>,
+    },
+    Focused {
+        window: Option<WindowInfo>,
+    },
+    /// The action was run
+    Done,
+    Subscribed,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    WindowOpened {
+        window: WindowInfo,
+    },
+    WindowClosed {
+        id: u64,
+    },
+    /// Keyboard focus moved to another window, or to something that is not a window
+    WindowFocused {
+        window: Option<WindowInfo>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowInfo {
+    /// Stays the same while the window exists
+    pub id: u64,
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    pub workspace: Option<usize>,
+    pub focused: bool,
+    pub floating: bool,
+    pub fullscreen: bool,
+    pub maximized: bool,
+    pub minimized: bool,
+    /// Location and size in the global space, if the window is mapped
+    pub geometry: Option<Geometry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputInfo {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub geometry: Option<Geometry>,
+    /// Refresh rate in mHz
+    pub refresh: Option<i32>,
+    pub scale: f64,
+    /// Workspace shown on the output
+    pub workspace: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceInfo {
+    pub number: usize,
+    /// Output the workspace is shown on
+    pub output: Option<String>,
+    pub layout: String,
+    /// Ids of the windows in layout order
+    pub windows: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl From<smithay::utils::Rectangle<i32, smithay::utils::Logical>> for Geometry {
+    fn from(rectangle: smithay::utils::Rectangle<i32, smithay::utils::Logical>) -> Self {
+        Self {
+            x: rectangle.loc.x,
+            y: rectangle.loc.y,
+            width: rectangle.size.w,
+            height: rectangle.size.h,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::action::{Action, Target};
+use smithay::input::keyboard::{Keycode, Keysym, ModifiersState};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+}
+
+impl From<&ModifiersState> for Modifiers {
+    fn from(state: &ModifiersState) -> Self {
+        Self {
+            ctrl: state.ctrl,
+            alt: state.alt,
+            shift: state.shift,
+            logo: state.logo,
+        }
+    }
+}
+
+/// Combination of held modifiers and the unmodified keysym of the pressed key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub modifiers: Modifiers,
+    pub keysym: Keysym,
+}
+
+impl KeyChord {
+    pub fn new(modifiers: Modifiers, keysym: Keysym) -> Self {
+        Self { modifiers, keysym }
+    }
+}
+
+pub struct Keybindings {
+    pub bindings: HashMap<KeyChord, Action>,
+    /// Keys whose press was intercepted, so their release has to be intercepted as well
+    intercepted: HashSet<Keycode>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        let logo = Modifiers {
+            logo: true,
+            ..Default::default()
+        };
+        let logo_shift = Modifiers {
+            shift: true,
+            ..logo
+        };
+
+        let mut bindings = HashMap::from([
+            (
+                KeyChord::new(logo, Keysym::Return),
+                Action::Spawn("foot".to_string()),
+            ),
+            (
+                KeyChord::new(logo, Keysym::q),
+                Action::Close(Target::Focused),
+            ),
+            (KeyChord::new(logo, Keysym::j), Action::FocusNext),
+            (KeyChord::new(logo, Keysym::k), Action::FocusPrevious),
+            (
+                KeyChord::new(logo, Keysym::f),
+                Action::ToggleFullscreen(Target::Focused),
+            ),
+            (KeyChord::new(logo, Keysym::space), Action::NextLayout),
+            (
+                KeyChord::new(logo_shift, Keysym::space),
+                Action::ToggleFloating(Target::Focused),
+            ),
+            (
+                KeyChord::new(logo, Keysym::h),
+                Action::AdjustMasterRatio(-5),
+            ),
+            (KeyChord::new(logo, Keysym::l), Action::AdjustMasterRatio(5)),
+            (KeyChord::new(logo, Keysym::minus), Action::AdjustGaps(-2)),
+            (KeyChord::new(logo, Keysym::equal), Action::AdjustGaps(2)),
+            (KeyChord::new(logo_shift, Keysym::e), Action::Quit),
+            (KeyChord::new(logo_shift, Keysym::r), Action::ReloadConfig),
+        ]);
+
+        // The number keys switch to their workspace or move the focused window there
+        for number in 1..=9 {
+            let keysym = Keysym::new(Keysym::_0.raw() + number as u32);
+            bindings.insert(KeyChord::new(logo, keysym), Action::SwitchWorkspace(number));
+            bindings.insert(
+                KeyChord::new(logo_shift, keysym),
+                Action::MoveToWorkspace(Target::Focused, number),
+            );
+        }
+
+        Self {
+            bindings,
+            intercepted: HashSet::new(),
+        }
+    }
+}
+
+impl Keybindings {
+    /// Returns the action bound to the pressed key and remembers the key as intercepted
+    pub fn press(&mut self, keycode: Keycode, chord: KeyChord) -> Option<Action> {
+        let action = self.bindings.get(&chord).cloned()?;
+        self.intercepted.insert(keycode);
+        Some(action)
+    }
+
+    /// Returns whether the press of the released key was intercepted
+    pub fn release(&mut self, keycode: Keycode) -> bool {
+        self.intercepted.remove(&keycode)
+    }
+}
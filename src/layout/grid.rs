@@ -0,0 +1,93 @@
+use super::{Geometry, Layout, Options};
+use smithay::utils::Rectangle;
+
+/// Rows of equally sized windows, the last row is stretched if it is not full
+pub struct Grid;
+
+impl Layout for Grid {
+    fn name(&self) -> &'static str {
+        "Grid"
+    }
+
+    fn arrange(
+        &self,
+        area: Geometry,
+        count: usize,
+        _focused: Option<usize>,
+        _options: &Options,
+    ) -> Vec<Option<Geometry>> {
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let columns = (count as f32).sqrt().ceil() as usize;
+        let rows = count.div_ceil(columns);
+
+        (0..count)
+            .map(|index| {
+                let row = index / columns;
+                let column = index % columns;
+                let row_columns = if row == rows - 1 {
+                    count - row * columns
+                } else {
+                    columns
+                };
+
+                let (x, width) = super::divide(area.size.w, row_columns, column);
+                let (y, height) = super::divide(area.size.h, rows, row);
+                Some(Rectangle::from_loc_and_size(
+                    (area.loc.x + x, area.loc.y + y),
+                    (width, height),
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::tests::{area, assert_covers, geometry};
+
+    fn arrange(count: usize) -> Vec<Option<Geometry>> {
+        Grid.arrange(area(), count, None, &Options::default())
+    }
+
+    #[test]
+    fn no_windows() {
+        assert!(arrange(0).is_empty());
+    }
+
+    #[test]
+    fn single_window_fills_the_area() {
+        assert_eq!(arrange(1), vec![Some(area())]);
+    }
+
+    #[test]
+    fn last_row_is_stretched() {
+        assert_eq!(
+            arrange(3),
+            vec![
+                geometry(10, 20, 50, 25),
+                geometry(60, 20, 50, 25),
+                geometry(10, 45, 100, 25),
+            ]
+        );
+    }
+
+    #[test]
+    fn odd_count_spreads_the_rounding() {
+        let geometries = arrange(5);
+        assert_eq!(
+            geometries,
+            vec![
+                geometry(10, 20, 33, 25),
+                geometry(43, 20, 33, 25),
+                geometry(76, 20, 34, 25),
+                geometry(10, 45, 50, 25),
+                geometry(60, 45, 50, 25),
+            ]
+        );
+        assert_covers(area(), &geometries);
+    }
+}
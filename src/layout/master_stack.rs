@@ -0,0 +1,88 @@
+use super::{Geometry, Layout, Options};
+use smithay::utils::Rectangle;
+
+/// The first window on the left, the others stacked on the right
+pub struct MasterStack;
+
+impl Layout for MasterStack {
+    fn name(&self) -> &'static str {
+        "Master-stack"
+    }
+
+    fn arrange(
+        &self,
+        area: Geometry,
+        count: usize,
+        _focused: Option<usize>,
+        options: &Options,
+    ) -> Vec<Option<Geometry>> {
+        if count <= 1 {
+            return (0..count).map(|_| Some(area)).collect();
+        }
+
+        let master_width = (area.size.w as f32 * options.master_ratio).round() as i32;
+        let stack_count = count - 1;
+
+        let master = Rectangle::from_loc_and_size(area.loc, (master_width, area.size.h));
+        let stack = (0..stack_count).map(|index| {
+            let (y, height) = super::divide(area.size.h, stack_count, index);
+            Rectangle::from_loc_and_size(
+                (area.loc.x + master_width, area.loc.y + y),
+                (area.size.w - master_width, height),
+            )
+        });
+
+        std::iter::once(master).chain(stack).map(Some).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::tests::{area, assert_covers, geometry};
+
+    fn arrange(count: usize, master_ratio: f32) -> Vec<Option<Geometry>> {
+        let options = Options {
+            master_ratio,
+            ..Options::default()
+        };
+        MasterStack.arrange(area(), count, None, &options)
+    }
+
+    #[test]
+    fn no_windows() {
+        assert!(arrange(0, 0.5).is_empty());
+    }
+
+    #[test]
+    fn single_window_fills_the_area() {
+        assert_eq!(arrange(1, 0.5), vec![Some(area())]);
+    }
+
+    #[test]
+    fn master_follows_the_ratio() {
+        assert_eq!(
+            arrange(2, 0.3),
+            vec![geometry(10, 20, 30, 50), geometry(40, 20, 70, 50)]
+        );
+        assert_eq!(
+            arrange(2, 0.75),
+            vec![geometry(10, 20, 75, 50), geometry(85, 20, 25, 50)]
+        );
+    }
+
+    #[test]
+    fn odd_stack_spreads_the_rounding() {
+        let geometries = arrange(4, 0.5);
+        assert_eq!(
+            geometries,
+            vec![
+                geometry(10, 20, 50, 50),
+                geometry(60, 20, 50, 16),
+                geometry(60, 36, 50, 17),
+                geometry(60, 53, 50, 17),
+            ]
+        );
+        assert_covers(area(), &geometries);
+    }
+}
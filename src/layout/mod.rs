@@ -0,0 +1,262 @@
+use smithay::{
+    desktop,
+    utils::{Logical, Rectangle},
+};
+
+mod grid;
+mod master_stack;
+mod monocle;
+mod spiral;
+
+pub type Geometry = Rectangle<i32, Logical>;
+
+/// Places windows inside the area available to them
+pub trait Layout: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns the geometry of each of the windows in order, or [None] if the window is hidden
+    fn arrange(
+        &self,
+        area: Geometry,
+        count: usize,
+        focused: Option<usize>,
+        options: &Options,
+    ) -> Vec<Option<Geometry>>;
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutKind {
+    #[default]
+    MasterStack,
+    Spiral,
+    Monocle,
+    Grid,
+}
+
+impl LayoutKind {
+    pub const ALL: [Self; 4] = [Self::MasterStack, Self::Spiral, Self::Monocle, Self::Grid];
+
+    pub fn layout(self) -> &'static dyn Layout {
+        match self {
+            Self::MasterStack => &master_stack::MasterStack,
+            Self::Spiral => &spiral::Spiral,
+            Self::Monocle => &monocle::Monocle,
+            Self::Grid => &grid::Grid,
+        }
+    }
+
+    /// The following layout, wrapping around after the last one
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|kind| kind == &self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Adjustable parameters shared by the layouts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    /// Share of the area given to the master window
+    pub master_ratio: f32,
+    /// Space between windows and along the edges of the area
+    pub gaps: i32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            master_ratio: 0.55,
+            gaps: 4,
+        }
+    }
+}
+
+/// The active layout and its options
+#[derive(Debug, Default)]
+pub struct LayoutState {
+    pub kind: LayoutKind,
+    pub options: Options,
+}
+
+impl LayoutState {
+    pub fn arrange(
+        &self,
+        area: Geometry,
+        count: usize,
+        focused: Option<usize>,
+    ) -> Vec<Option<Geometry>> {
+        // Half a gap around the area and every window adds up to a full gap between them.
+        // Odd gaps are split unevenly, so the halves before and after still add up to the gap
+        let small_half = self.options.gaps / 2;
+        let large_half = self.options.gaps - small_half;
+
+        self.kind
+            .layout()
+            .arrange(
+                shrink(area, small_half, large_half),
+                count,
+                focused,
+                &self.options,
+            )
+            .into_iter()
+            .map(|geometry| geometry.map(|geometry| shrink(geometry, large_half, small_half)))
+            .collect()
+    }
+
+    pub fn adjust_master_ratio(&mut self, delta: f32) {
+        self.options.master_ratio = (self.options.master_ratio + delta).clamp(0.1, 0.9);
+    }
+
+    pub fn adjust_gaps(&mut self, delta: i32) {
+        self.options.gaps = (self.options.gaps + delta).max(0);
+    }
+}
+
+/// Window placed by the user instead of the layout
+#[derive(Debug, Clone)]
+pub struct Floating {
+    pub window: desktop::Window,
+    /// Global location and size, including the title bar drawn by the shell
+    pub geometry: Geometry,
+}
+
+/// Moves the left and top edges inwards by the first amount and the others by the second one
+fn shrink(geometry: Geometry, before: i32, after: i32) -> Geometry {
+    Rectangle::from_loc_and_size(
+        (geometry.loc.x + before, geometry.loc.y + before),
+        (
+            (geometry.size.w - before - after).max(0),
+            (geometry.size.h - before - after).max(0),
+        ),
+    )
+}
+
+/// Start and length of the part with the index when dividing the length into equal parts.
+/// Rounding is spread over the parts so they always cover the whole length
+fn divide(length: i32, count: usize, index: usize) -> (i32, i32) {
+    let start = length * index as i32 / count as i32;
+    let end = length * (index as i32 + 1) / count as i32;
+    (start, end - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Area the layouts are tested with, away from the origin to catch missing offsets
+    pub fn area() -> Geometry {
+        Rectangle::from_loc_and_size((10, 20), (100, 50))
+    }
+
+    pub fn geometry(x: i32, y: i32, width: i32, height: i32) -> Option<Geometry> {
+        Some(Rectangle::from_loc_and_size((x, y), (width, height)))
+    }
+
+    /// Asserts that the shown windows cover the area without overlapping
+    pub fn assert_covers(area: Geometry, geometries: &[Option<Geometry>]) {
+        let shown: Vec<_> = geometries.iter().flatten().collect();
+        for (index, geometry) in shown.iter().enumerate() {
+            assert!(
+                area.contains_rect(**geometry),
+                "{geometry:?} is outside {area:?}"
+            );
+            for other in &shown[index + 1..] {
+                assert!(
+                    !geometry.overlaps(**other),
+                    "{geometry:?} overlaps {other:?}"
+                );
+            }
+        }
+
+        let covered: i32 = shown
+            .iter()
+            .map(|geometry| geometry.size.w * geometry.size.h)
+            .sum();
+        assert_eq!(covered, area.size.w * area.size.h);
+    }
+
+    fn master_stack(gaps: i32) -> LayoutState {
+        LayoutState {
+            kind: LayoutKind::MasterStack,
+            options: Options {
+                gaps,
+                ..Options::default()
+            },
+        }
+    }
+
+    #[test]
+    fn no_gaps_keep_the_layout() {
+        let state = master_stack(0);
+        assert_eq!(
+            state.arrange(area(), 3, None),
+            LayoutKind::MasterStack
+                .layout()
+                .arrange(area(), 3, None, &state.options)
+        );
+    }
+
+    #[test]
+    fn gaps_between_windows_and_along_the_edges() {
+        let geometries =
+            master_stack(4).arrange(Rectangle::from_loc_and_size((0, 0), (100, 50)), 2, None);
+        assert_eq!(
+            geometries,
+            vec![geometry(4, 4, 49, 42), geometry(57, 4, 39, 42)]
+        );
+    }
+
+    #[test]
+    fn odd_gaps_are_not_rounded_down() {
+        let geometries =
+            master_stack(5).arrange(Rectangle::from_loc_and_size((0, 0), (100, 50)), 2, None);
+        assert_eq!(
+            geometries,
+            vec![geometry(5, 5, 47, 40), geometry(57, 5, 38, 40)]
+        );
+    }
+
+    #[test]
+    fn gaps_larger_than_the_area_leave_empty_windows() {
+        let geometries = master_stack(200).arrange(area(), 2, None);
+        assert!(geometries
+            .iter()
+            .flatten()
+            .all(|geometry| geometry.size.w == 0 && geometry.size.h == 0));
+    }
+
+    #[test]
+    fn master_ratio_and_gaps_are_clamped() {
+        let mut state = master_stack(2);
+        state.adjust_master_ratio(1.0);
+        assert_eq!(state.options.master_ratio, 0.9);
+        state.adjust_master_ratio(-2.0);
+        assert_eq!(state.options.master_ratio, 0.1);
+
+        state.adjust_gaps(-5);
+        assert_eq!(state.options.gaps, 0);
+    }
+
+    #[test]
+    fn next_layout_wraps_around() {
+        let mut kind = LayoutKind::default();
+        for _ in 0..LayoutKind::ALL.len() {
+            kind = kind.next();
+        }
+        assert_eq!(kind, LayoutKind::default());
+    }
+
+    #[test]
+    fn every_layout_covers_the_area() {
+        for kind in LayoutKind::ALL {
+            for count in 0..8 {
+                let geometries = kind
+                    .layout()
+                    .arrange(area(), count, None, &Options::default());
+                assert_eq!(geometries.len(), count, "{}", kind.layout().name());
+                if count > 0 {
+                    assert_covers(area(), &geometries);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use super::{Geometry, Layout, Options};
+
+/// Only the focused window is shown, taking the whole area
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn name(&self) -> &'static str {
+        "Monocle"
+    }
+
+    fn arrange(
+        &self,
+        area: Geometry,
+        count: usize,
+        focused: Option<usize>,
+        _options: &Options,
+    ) -> Vec<Option<Geometry>> {
+        let shown = focused.unwrap_or(0);
+
+        (0..count)
+            .map(|index| (index == shown).then_some(area))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::tests::area;
+
+    fn arrange(count: usize, focused: Option<usize>) -> Vec<Option<Geometry>> {
+        Monocle.arrange(area(), count, focused, &Options::default())
+    }
+
+    #[test]
+    fn no_windows() {
+        assert!(arrange(0, None).is_empty());
+    }
+
+    #[test]
+    fn single_window_fills_the_area() {
+        assert_eq!(arrange(1, None), vec![Some(area())]);
+    }
+
+    #[test]
+    fn only_the_focused_window_is_shown() {
+        assert_eq!(arrange(3, Some(1)), vec![None, Some(area()), None]);
+    }
+
+    #[test]
+    fn first_window_is_shown_without_focus() {
+        assert_eq!(arrange(3, None), vec![Some(area()), None, None]);
+    }
+}
@@ -0,0 +1,114 @@
+use super::{Geometry, Layout, Options};
+use smithay::utils::Rectangle;
+
+/// Every window takes the left or top part of the remaining area and leaves the rest to the
+/// following windows, alternating between horizontal and vertical splits
+pub struct Spiral;
+
+impl Layout for Spiral {
+    fn name(&self) -> &'static str {
+        "Spiral"
+    }
+
+    fn arrange(
+        &self,
+        area: Geometry,
+        count: usize,
+        _focused: Option<usize>,
+        options: &Options,
+    ) -> Vec<Option<Geometry>> {
+        let mut remaining = area;
+        let mut geometries = Vec::with_capacity(count);
+
+        for index in 0..count {
+            if index == count - 1 {
+                geometries.push(Some(remaining));
+                break;
+            }
+
+            // Only the root split follows the master ratio
+            let ratio = if index == 0 {
+                options.master_ratio
+            } else {
+                0.5
+            };
+
+            let (taken, rest) = if index % 2 == 0 {
+                let width = (remaining.size.w as f32 * ratio).round() as i32;
+                (
+                    Rectangle::from_loc_and_size(remaining.loc, (width, remaining.size.h)),
+                    Rectangle::from_loc_and_size(
+                        (remaining.loc.x + width, remaining.loc.y),
+                        (remaining.size.w - width, remaining.size.h),
+                    ),
+                )
+            } else {
+                let height = (remaining.size.h as f32 * ratio).round() as i32;
+                (
+                    Rectangle::from_loc_and_size(remaining.loc, (remaining.size.w, height)),
+                    Rectangle::from_loc_and_size(
+                        (remaining.loc.x, remaining.loc.y + height),
+                        (remaining.size.w, remaining.size.h - height),
+                    ),
+                )
+            };
+
+            geometries.push(Some(taken));
+            remaining = rest;
+        }
+
+        geometries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::tests::{area, assert_covers, geometry};
+
+    fn arrange(count: usize, master_ratio: f32) -> Vec<Option<Geometry>> {
+        let options = Options {
+            master_ratio,
+            ..Options::default()
+        };
+        Spiral.arrange(area(), count, None, &options)
+    }
+
+    #[test]
+    fn no_windows() {
+        assert!(arrange(0, 0.5).is_empty());
+    }
+
+    #[test]
+    fn single_window_fills_the_area() {
+        assert_eq!(arrange(1, 0.5), vec![Some(area())]);
+    }
+
+    #[test]
+    fn only_the_first_split_follows_the_ratio() {
+        assert_eq!(
+            arrange(3, 0.3),
+            vec![
+                geometry(10, 20, 30, 50),
+                geometry(40, 20, 70, 25),
+                geometry(40, 45, 70, 25),
+            ]
+        );
+    }
+
+    #[test]
+    fn odd_count_alternates_the_splits() {
+        let geometries = arrange(5, 0.5);
+        assert_eq!(
+            geometries,
+            vec![
+                geometry(10, 20, 50, 50),
+                geometry(60, 20, 50, 25),
+                geometry(60, 45, 25, 25),
+                geometry(85, 45, 25, 13),
+                geometry(85, 58, 25, 12),
+            ]
+        );
+        assert_covers(area(), &geometries);
+    }
+}
@@ -1,11 +1,20 @@
+pub mod action;
 pub mod backends;
+pub mod config;
+pub mod grabs;
 pub mod handlers;
 pub mod iced;
 pub mod input;
+pub mod ipc;
+pub mod keybindings;
+pub mod layout;
+pub mod redraw;
+pub mod render;
 pub mod shell;
 pub mod socket;
 pub mod state;
 pub mod util;
+pub mod workspace;
 
 mod run;
 
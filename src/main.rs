@@ -5,5 +5,9 @@ fn main() {
         tracing_subscriber::fmt().init();
     }
 
-    chadland::run::<chadland::backends::winit::WinitBackend>();
+    if std::env::args().any(|arg| arg == "--headless") {
+        chadland::run::<chadland::backends::headless::HeadlessBackend>();
+    } else {
+        chadland::run::<chadland::backends::winit::WinitBackend>();
+    }
 }
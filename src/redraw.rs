@@ -0,0 +1,63 @@
+use smithay::reexports::calloop::{
+    self,
+    timer::{TimeoutAction, Timer},
+};
+use std::time::{Duration, Instant};
+use tracing::error;
+
+/// Coalesces redraw requests into frames paced at the refresh rate of the outputs.
+/// Nothing is rendered while no one asks for a redraw
+#[derive(Default)]
+pub struct Scheduler {
+    /// When the timer of the next frame fires
+    pending: Option<(Instant, calloop::RegistrationToken)>,
+    last_frame: Option<Instant>,
+}
+
+impl<B: crate::Backend> crate::state::Compositor<B> {
+    /// Redraws the outputs as soon as the refresh rate allows
+    pub fn schedule_redraw(&mut self) {
+        self.schedule_redraw_at(Instant::now());
+    }
+
+    /// Redraws the outputs at the instant, but not before the next refresh.
+    /// Only the earliest of multiple requests is kept
+    pub fn schedule_redraw_at(&mut self, at: Instant) {
+        let next_refresh = self
+            .redraw
+            .last_frame
+            .map(|last_frame| last_frame + self.frame_duration());
+        let at = next_refresh.map_or(at, |next_refresh| at.max(next_refresh));
+
+        if let Some((pending, token)) = self.redraw.pending {
+            if pending <= at {
+                return;
+            }
+            self.loop_handle.remove(token);
+        }
+
+        let timer = self
+            .loop_handle
+            .insert_source(Timer::from_deadline(at), |_, _, app| {
+                app.common.comp.redraw.pending = None;
+                app.common.comp.redraw.last_frame = Some(Instant::now());
+                B::render(app);
+                TimeoutAction::Drop
+            });
+        match timer {
+            Ok(token) => self.redraw.pending = Some((at, token)),
+            Err(err) => error!("Unable to insert redraw timer: {}", err.error),
+        }
+    }
+
+    /// Time between two frames on the output with the highest refresh rate
+    pub fn frame_duration(&self) -> Duration {
+        self.space
+            .outputs()
+            .map(|output| self.config.frame_duration(&output.name()))
+            .min()
+            .unwrap_or(Duration::from_millis(
+                1000 / crate::config::DEFAULT_REFRESH_RATE as u64,
+            ))
+    }
+}
@@ -0,0 +1,438 @@
+use smithay::{
+    backend::{
+        allocator::dmabuf::Dmabuf,
+        renderer::{
+            element::{
+                default_primary_scanout_output_compare, render_elements,
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                texture::{TextureRenderBuffer, TextureRenderElement},
+                Element, Id, Kind, RenderElementStates,
+            },
+            gles::{GlesRenderer, GlesTexture},
+            ImportDma,
+        },
+    },
+    desktop::{
+        layer_map_for_output,
+        utils::{
+            send_frames_surface_tree, surface_presentation_feedback_flags_from_states,
+            surface_primary_scanout_output, take_presentation_feedback_surface_tree,
+            update_surface_primary_scanout_output, OutputPresentationFeedback,
+        },
+        PopupManager,
+    },
+    output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Buffer, Physical, Point, Rectangle, Transform},
+    wayland::{
+        compositor::{self, SurfaceData, TraversalAction},
+        fractional_scale::with_fractional_scale,
+        presentation::Refresh,
+        shell::wlr_layer::Layer,
+    },
+};
+use std::time::Duration;
+
+/// The shell buffer imported into the renderer.
+/// Keeps the damage of the shell for the damage trackers
+pub struct ShellBuffer {
+    dmabuf: Dmabuf,
+    buffer: TextureRenderBuffer<GlesTexture>,
+    /// Id of the elements drawing the buffer, once there was one
+    id: Option<Id>,
+}
+
+impl ShellBuffer {
+    /// Whether the shell was visible in the rendered frame
+    fn presented(&self, states: &RenderElementStates) -> bool {
+        self.id
+            .as_ref()
+            .is_some_and(|id| states.element_was_presented(id.clone()))
+    }
+}
+render_elements! {
+    pub OutputRenderElement<=GlesRenderer>;
+    Surface=WaylandSurfaceRenderElement<GlesRenderer>,
+    Shell=TextureRenderElement<GlesTexture>,
+}
+
+/// Collects everything that should be drawn on the output, topmost element first
+pub fn output_elements<B: crate::Backend>(
+    renderer: &mut GlesRenderer,
+    output: &output::Output,
+    common: &mut crate::state::Common<B>,
+) -> Vec<OutputRenderElement> {
+    let mut elements = Vec::new();
+
+    // Rendering the shell lays it out, which moves the windows into place.
+    // So it has to happen before any window positions are used for popups
+    let shell_element = (common.comp.shell_output() == Some(output))
+        .then(|| shell_element(renderer, output, common))
+        .flatten();
+
+    let output_location = common
+        .comp
+        .space
+        .output_geometry(output)
+        .map(|geometry| geometry.loc)
+        .unwrap_or_default();
+    let scale = output.current_scale().fractional_scale();
+
+    // The drag-and-drop icon follows the pointer above everything else
+    if let Some(icon) = &common.comp.dnd_icon {
+        let location =
+            common.comp.pointer_location + icon.offset.to_f64() - output_location.to_f64();
+        elements.extend(surface_elements(
+            renderer,
+            &icon.surface,
+            location.to_physical_precise_round(scale),
+            scale,
+        ));
+    }
+
+    // Menus and tooltips of X11 clients are placed above everything by the clients
+    for window in common.comp.override_redirect.iter().rev() {
+        if let Some(surface) = window.wl_surface() {
+            let location = window.geometry().loc - output_location;
+            elements.extend(surface_elements(
+                renderer,
+                &surface,
+                location.to_physical_precise_round(scale),
+                scale,
+            ));
+        }
+    }
+
+    let layer_map = layer_map_for_output(output);
+    let layers = |kind: Layer| {
+        layer_map.layers_on(kind).rev().filter_map(|layer| {
+            layer_map
+                .layer_geometry(layer)
+                .map(|geometry| (layer.wl_surface().clone(), geometry.loc))
+        })
+    };
+    let overlay_layers: Vec<_> = layers(Layer::Overlay).collect();
+    let top_layers: Vec<_> = layers(Layer::Top).collect();
+    let lower_layers: Vec<_> = layers(Layer::Bottom)
+        .chain(layers(Layer::Background))
+        .collect();
+    let windows: Vec<_> = common
+        .comp
+        .space
+        .elements()
+        .rev()
+        .filter_map(|window| {
+            let location = common.comp.space.element_location(window)? - output_location;
+            Some((crate::util::surface::window_surface(window)?, location))
+        })
+        .collect();
+    drop(layer_map);
+
+    // Popups are drawn above the shell, as they may extend past the surface they belong to
+    let popup_parents = overlay_layers
+        .iter()
+        .chain(&top_layers)
+        .chain(&lower_layers)
+        .chain(&windows)
+        .map(|(surface, location)| (surface, *location));
+    for (parent, parent_location) in popup_parents {
+        for (popup, popup_offset) in PopupManager::popups_for_surface(parent) {
+            let location = parent_location + popup_offset - popup.geometry().loc;
+            elements.extend(surface_elements(
+                renderer,
+                popup.wl_surface(),
+                location.to_physical_precise_round(scale),
+                scale,
+            ));
+        }
+    }
+
+    for (surface, location) in &overlay_layers {
+        elements.extend(surface_elements(
+            renderer,
+            surface,
+            location.to_physical_precise_round(scale),
+            scale,
+        ));
+    }
+
+    // Fullscreen windows cover the shell and its bars
+    if let Some(window) = &common.comp.workspace().fullscreen {
+        if let (Some(surface), Some(location)) = (
+            crate::util::surface::window_surface(window),
+            common.comp.space.element_location(window),
+        ) {
+            let location = location - output_location - window.geometry().loc;
+            elements.extend(surface_elements(
+                renderer,
+                &surface,
+                location.to_physical_precise_round(scale),
+                scale,
+            ));
+        }
+    }
+
+    for (surface, location) in &top_layers {
+        elements.extend(surface_elements(
+            renderer,
+            surface,
+            location.to_physical_precise_round(scale),
+            scale,
+        ));
+    }
+
+    // The shell draws the windows itself
+    elements.extend(shell_element.map(OutputRenderElement::Shell));
+
+    for (surface, location) in &lower_layers {
+        elements.extend(surface_elements(
+            renderer,
+            surface,
+            location.to_physical_precise_round(scale),
+            scale,
+        ));
+    }
+
+    elements
+}
+
+fn surface_elements(
+    renderer: &mut GlesRenderer,
+    surface: &WlSurface,
+    location: Point<i32, Physical>,
+    scale: f64,
+) -> impl Iterator<Item = OutputRenderElement> {
+    let elements: Vec<WaylandSurfaceRenderElement<_>> = render_elements_from_surface_tree(
+        renderer,
+        surface,
+        location,
+        scale,
+        1.0,
+        Kind::Unspecified,
+    );
+    elements.into_iter().map(OutputRenderElement::Surface)
+}
+
+fn shell_element<B: crate::Backend>(
+    renderer: &mut GlesRenderer,
+    output: &output::Output,
+    common: &mut crate::state::Common<B>,
+) -> Option<TextureRenderElement<GlesTexture>> {
+    let output_location = common.comp.space.output_geometry(output)?.loc;
+    // The shell only covers the area left by exclusive zones of layer surfaces
+    let shell_geometry = common.comp.shell_geometry()?;
+    if shell_geometry.size.w <= 0 || shell_geometry.size.h <= 0 {
+        return None;
+    }
+
+    // The shell is laid out in logical coordinates and rendered at the output scale
+    let scale = output.current_scale().fractional_scale();
+    let bounds = (shell_geometry.size.w as u32, shell_geometry.size.h as u32).into();
+
+    let (iced_dmabuf, damage) = common.shell_driver.render(&mut common.comp, bounds, scale);
+
+    // The buffer is reused while the shell size stays the same, so it only has to be imported once.
+    // A new buffer gets a new id, which makes the damage trackers draw all of it
+    let mut shell_buffer = match common.shell_buffer.take() {
+        Some(shell_buffer) if shell_buffer.dmabuf == iced_dmabuf => shell_buffer,
+        _ => {
+            let iced_texture = renderer
+                .import_dmabuf(&iced_dmabuf, None)
+                .expect("Cant import iced dmabuf into gles");
+            ShellBuffer {
+                dmabuf: iced_dmabuf,
+                buffer: TextureRenderBuffer::from_texture(
+                    renderer,
+                    iced_texture,
+                    1,
+                    Transform::Normal,
+                    None,
+                ),
+                id: None,
+            }
+        }
+    };
+
+    let damage: Vec<Rectangle<i32, Buffer>> = damage
+        .into_iter()
+        .map(|rect| {
+            Rectangle::from_loc_and_size(
+                (rect.x as i32, rect.y as i32),
+                (rect.width as i32, rect.height as i32),
+            )
+        })
+        .collect();
+    let _ = shell_buffer
+        .buffer
+        .render()
+        .draw(|_| Ok::<_, std::convert::Infallible>(damage));
+
+    let location = (shell_geometry.loc - output_location)
+        .to_f64()
+        .to_physical(scale);
+
+    let element = TextureRenderElement::from_texture_render_buffer(
+        location,
+        &shell_buffer.buffer,
+        None,
+        None,
+        // The texture has the physical size, which is this logical size at the output scale
+        Some(shell_geometry.size),
+        Kind::Unspecified,
+    );
+    shell_buffer.id = Some(element.id().clone());
+    common.shell_buffer = Some(shell_buffer);
+
+    Some(element)
+}
+
+/// Sends frame callbacks to the surfaces that were presented on the output
+/// and collects the presentation feedback of their commits
+pub fn post_repaint<B: crate::Backend>(
+    common: &crate::state::Common<B>,
+    output: &output::Output,
+    states: &RenderElementStates,
+) -> OutputPresentationFeedback {
+    let comp = &common.comp;
+    let update = |surface: &WlSurface, surface_data: &SurfaceData| {
+        update_surface_primary_scanout_output(
+            surface,
+            output,
+            surface_data,
+            states,
+            default_primary_scanout_output_compare,
+        );
+    };
+    let update_tree = |surface: &WlSurface| {
+        compositor::with_surface_tree_downward(
+            surface,
+            (),
+            |_, _, _| TraversalAction::DoChildren(()),
+            |surface, surface_data, _| update(surface, surface_data),
+            |_, _, _| true,
+        );
+    };
+
+    // Windows of hidden workspaces are not mapped, so they are never presented
+    for window in comp.space.elements() {
+        window.with_surfaces(update);
+    }
+    for window in &comp.override_redirect {
+        if let Some(surface) = window.wl_surface() {
+            update_tree(&surface);
+        }
+    }
+    for layer in layer_map_for_output(output).layers() {
+        layer.with_surfaces(update);
+    }
+    if let Some(icon) = &comp.dnd_icon {
+        update_tree(&icon.surface);
+    }
+
+    // The shell draws the windows itself, so they are shown whenever the shell is.
+    // A fullscreen window and the popups are elements of their own
+    let shell_presented = comp.shell_output() == Some(output)
+        && common
+            .shell_buffer
+            .as_ref()
+            .is_some_and(|shell_buffer| shell_buffer.presented(states));
+    let fullscreen = comp.workspace().fullscreen.as_ref();
+    let window_output = |drawn_by_shell: bool| {
+        move |surface: &WlSurface, surface_data: &SurfaceData| {
+            surface_primary_scanout_output(surface, surface_data)
+                .or_else(|| drawn_by_shell.then(|| output.clone()))
+        }
+    };
+    let feedback_flags = |surface: &WlSurface, _: &SurfaceData| {
+        surface_presentation_feedback_flags_from_states(surface, states)
+    };
+
+    let time = comp.start_time.elapsed();
+    let mut feedback = OutputPresentationFeedback::new(output);
+
+    for window in comp.space.elements() {
+        let primary_output = window_output(shell_presented && fullscreen != Some(window));
+        window.send_frame(output, time, None, primary_output);
+        window.take_presentation_feedback(&mut feedback, primary_output, feedback_flags);
+    }
+
+    for layer in layer_map_for_output(output).layers() {
+        layer.send_frame(output, time, None, surface_primary_scanout_output);
+        layer.take_presentation_feedback(
+            &mut feedback,
+            surface_primary_scanout_output,
+            feedback_flags,
+        );
+    }
+
+    let surface_trees = comp
+        .override_redirect
+        .iter()
+        .filter_map(|window| window.wl_surface())
+        .chain(comp.dnd_icon.as_ref().map(|icon| icon.surface.clone()));
+    for surface in surface_trees {
+        send_frames_surface_tree(&surface, output, time, None, surface_primary_scanout_output);
+        take_presentation_feedback_surface_tree(
+            &surface,
+            &mut feedback,
+            surface_primary_scanout_output,
+            feedback_flags,
+        );
+    }
+
+    feedback
+}
+
+/// Time between two refreshes of the output for presentation feedback
+pub fn refresh(output: &output::Output) -> Refresh {
+    output
+        .current_mode()
+        .map(|mode| Refresh::fixed(Duration::from_secs_f64(1000. / mode.refresh as f64)))
+        .unwrap_or(Refresh::Unknown)
+}
+
+/// Tells the surfaces shown on the output which scale to render at.
+/// Clients are only notified when the scale changed
+pub fn send_preferred_scales<B: crate::Backend>(
+    comp: &crate::state::Compositor<B>,
+    output: &output::Output,
+) {
+    let scale = output.current_scale();
+    let transform = output.current_transform();
+    let update = |surface: &WlSurface, states: &SurfaceData| {
+        compositor::send_surface_state(surface, states, scale.integer_scale(), transform);
+        with_fractional_scale(states, |fractional_scale| {
+            fractional_scale.set_preferred_scale(scale.fractional_scale());
+        });
+    };
+    let update_tree = |surface: &WlSurface| {
+        compositor::with_surface_tree_downward(
+            surface,
+            (),
+            |_, _, _| TraversalAction::DoChildren(()),
+            |surface, states, _| update(surface, states),
+            |_, _, _| true,
+        );
+    };
+
+    // Windows spanning several outputs use the scale of the first one
+    for window in comp.space.elements() {
+        if comp.space.outputs_for_element(window).first() == Some(output) {
+            window.with_surfaces(update);
+        }
+    }
+
+    for layer in layer_map_for_output(output).layers() {
+        layer.with_surfaces(update);
+    }
+
+    for window in &comp.override_redirect {
+        if let Some(surface) = window.wl_surface() {
+            update_tree(&surface);
+        }
+    }
+
+    if let Some(icon) = &comp.dnd_icon {
+        update_tree(&icon.surface);
+    }
+}
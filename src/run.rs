@@ -1,6 +1,4 @@
-use std::sync::Arc;
-
-use smithay::reexports::*;
+use smithay::reexports::{wayland_server::Resource as _, *};
 use tracing::info;
 
 /// Run the compositor using the specified [crate::Backend]
@@ -10,36 +8,52 @@ pub fn run<B: crate::Backend<SelfType = B>>() {
     let display: wayland_server::Display<crate::App<B>> =
         wayland_server::Display::new().expect("Unable to create wayland display");
 
-    let mut common = crate::state::Common {
-        comp: crate::state::Compositor::new(
-            display.handle(),
-            event_loop.handle(),
-            event_loop.get_signal(),
-        ),
-        shell_driver: crate::iced::Driver::new(
-            Arc::new(futures::executor::block_on(
-                crate::iced::wgpu::Objects::new(),
-            )),
-            event_loop.handle(),
-            |app| {
-                (
-                    &mut app.common.shell_driver,
-                    &mut app.common.comp,
-                    (500, 500).into(),
-                )
-            },
-        ),
-    };
+    let config = crate::config::Config::load_or_default();
+
+    let mut common = crate::state::Common::new(
+        display.handle(),
+        event_loop.handle(),
+        event_loop.get_signal(),
+        config.clone(),
+    );
     let backend = B::new(&mut common);
 
     let mut app = crate::App { common, backend };
+    app.apply_config(config);
 
     crate::socket::init_socket(&mut app, display);
+    crate::ipc::init_ipc(&mut app);
     init_dmabuf(&mut app);
+    crate::config::init_watcher(&app.common.comp.loop_handle);
+    app.start_xwayland();
+    // Later frames are only drawn when something changed
+    app.common.comp.schedule_redraw();
+
+    // Spawned after the socket exists so the commands can connect to it
+    for command in app.common.comp.config.autostart.clone() {
+        app.run_action(crate::action::Action::Spawn(command));
+    }
 
     event_loop
         .run(None, &mut app, |app| {
             app.common.comp.space.refresh();
+            app.common.comp.popups.cleanup();
+            app.common.comp.windows.retain(|window| window.alive());
+            app.common
+                .comp
+                .override_redirect
+                .retain(|window| window.alive());
+            let outputs: Vec<_> = app.common.comp.space.outputs().cloned().collect();
+            app.common.comp.workspaces.refresh(&outputs);
+            if let Some(false) = app
+                .common
+                .comp
+                .dnd_icon
+                .as_ref()
+                .map(|icon| icon.surface.is_alive())
+            {
+                app.common.comp.dnd_icon = None;
+            }
             app.common
                 .comp
                 .display_handle
@@ -0,0 +1,211 @@
+use crate::layout::Geometry;
+use iced_core::{event, layout, mouse, overlay, renderer, widget, Element};
+
+/// Places every child at the geometry returned for it when arranging the available area.
+/// Children without a geometry are hidden, they are laid out with a zero size and skipped
+pub struct Arranged<'a, Message, Theme, Renderer> {
+    arrange: Box<dyn Fn(Geometry) -> Vec<Option<Geometry>> + 'a>,
+    children: Vec<Element<'a, Message, Theme, Renderer>>,
+}
+
+impl<'a, Message, Theme, Renderer> Arranged<'a, Message, Theme, Renderer> {
+    /// The geometries have to be returned in the same order as the children
+    pub fn new(
+        arrange: impl Fn(Geometry) -> Vec<Option<Geometry>> + 'a,
+        children: Vec<Element<'a, Message, Theme, Renderer>>,
+    ) -> Self {
+        Self {
+            arrange: Box::new(arrange),
+            children,
+        }
+    }
+}
+
+fn visible(layout: &iced_core::Layout<'_>) -> bool {
+    let bounds = layout.bounds();
+    bounds.width > 0.0 && bounds.height > 0.0
+}
+
+/// Lays out the child inside the geometry
+fn place<Message, Theme, Renderer: iced_core::Renderer>(
+    child: &Element<'_, Message, Theme, Renderer>,
+    tree: &mut widget::Tree,
+    renderer: &Renderer,
+    geometry: Geometry,
+) -> layout::Node {
+    let limits = layout::Limits::new(
+        iced_core::Size::ZERO,
+        iced_core::Size::new(geometry.size.w as f32, geometry.size.h as f32),
+    );
+
+    child
+        .as_widget()
+        .layout(tree, renderer, &limits)
+        .move_to(iced_core::Point::new(
+            geometry.loc.x as f32,
+            geometry.loc.y as f32,
+        ))
+}
+
+impl<Message, Theme, Renderer: iced_core::Renderer> iced_core::Widget<Message, Theme, Renderer>
+    for Arranged<'_, Message, Theme, Renderer>
+{
+    fn size(&self) -> iced_core::Size<iced_core::Length> {
+        iced_core::Size::new(iced_core::Length::Fill, iced_core::Length::Fill)
+    }
+
+    fn children(&self) -> Vec<widget::Tree> {
+        self.children.iter().map(widget::Tree::new).collect()
+    }
+
+    fn diff(&self, tree: &mut widget::Tree) {
+        tree.diff_children(&self.children);
+    }
+
+    fn layout(
+        &self,
+        tree: &mut widget::Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let size = limits.resolve(
+            iced_core::Length::Fill,
+            iced_core::Length::Fill,
+            iced_core::Size::ZERO,
+        );
+        let area = smithay::utils::Rectangle::from_loc_and_size(
+            (0, 0),
+            (size.width as i32, size.height as i32),
+        );
+
+        let geometries = (self.arrange)(area);
+        let children = self
+            .children
+            .iter()
+            .zip(&mut tree.children)
+            .zip(geometries)
+            .map(|((child, tree), geometry)| match geometry {
+                Some(geometry) => place(child, tree, renderer, geometry),
+                None => layout::Node::new(iced_core::Size::ZERO),
+            })
+            .collect();
+
+        layout::Node::with_children(size, children)
+    }
+
+    fn operate(
+        &self,
+        tree: &mut widget::Tree,
+        layout: iced_core::Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn widget::Operation,
+    ) {
+        operation.container(None, layout.bounds(), &mut |operation| {
+            for ((child, state), layout) in self
+                .children
+                .iter()
+                .zip(&mut tree.children)
+                .zip(layout.children())
+                .filter(|(_, layout)| visible(layout))
+            {
+                child
+                    .as_widget()
+                    .operate(state, layout, renderer, operation);
+            }
+        });
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut widget::Tree,
+        event: iced_core::Event,
+        layout: iced_core::Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn iced_core::Clipboard,
+        shell: &mut iced_core::Shell<'_, Message>,
+        viewport: &iced_core::Rectangle,
+    ) -> event::Status {
+        self.children
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(layout.children())
+            .filter(|(_, layout)| visible(layout))
+            .map(|((child, state), layout)| {
+                child.as_widget_mut().on_event(
+                    state,
+                    event.clone(),
+                    layout,
+                    cursor,
+                    renderer,
+                    clipboard,
+                    shell,
+                    viewport,
+                )
+            })
+            .fold(event::Status::Ignored, event::Status::merge)
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &widget::Tree,
+        layout: iced_core::Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &iced_core::Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.children
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+            .filter(|(_, layout)| visible(layout))
+            .map(|((child, state), layout)| {
+                child
+                    .as_widget()
+                    .mouse_interaction(state, layout, cursor, viewport, renderer)
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn draw(
+        &self,
+        tree: &widget::Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: iced_core::Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &iced_core::Rectangle,
+    ) {
+        for ((child, state), layout) in self
+            .children
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+            .filter(|(_, layout)| visible(layout))
+        {
+            child
+                .as_widget()
+                .draw(state, renderer, theme, style, layout, cursor, viewport);
+        }
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut widget::Tree,
+        layout: iced_core::Layout<'_>,
+        renderer: &Renderer,
+        translation: iced_core::Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        overlay::from_children(&mut self.children, tree, layout, renderer, translation)
+    }
+}
+
+impl<'a, Message: 'a, Theme: 'a, Renderer: iced_core::Renderer + 'a>
+    From<Arranged<'a, Message, Theme, Renderer>> for Element<'a, Message, Theme, Renderer>
+{
+    fn from(arranged: Arranged<'a, Message, Theme, Renderer>) -> Self {
+        Element::new(arranged)
+    }
+}
@@ -1,32 +1,135 @@
+use crate::action::{Action, Target};
 use iced_core::{alignment::Vertical, Element, Length};
-use iced_widget::{button, column, horizontal_space, row, text, vertical_space};
+use iced_widget::{
+    button, column, container, horizontal_space, mouse_area, pick_list, row, stack, text,
+    text_input,
+};
+use smithay::{
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Rectangle, Size},
+    wayland::selection::SelectionTarget,
+};
 use std::marker::PhantomData;
+use tracing::error;
 
+mod arranged;
 mod window;
 
+/// Height of the title bars drawn by the shell
+const TITLE_BAR_HEIGHT: i32 = 36;
+
 #[derive(Debug, Clone)]
-pub enum Message {}
+pub enum Message {
+    FocusWindow(WlSurface),
+    FocusNext,
+    CloseWindow(WlSurface),
+    RaiseWindow(WlSurface),
+    MoveWindow(WlSurface),
+    ToggleFloating(WlSurface),
+    ToggleFullscreen(WlSurface),
+    ToggleMaximize(WlSurface),
+    MinimizeWindow(WlSurface),
+    SwitchWorkspace(usize),
+    MoveToWorkspace(WlSurface, usize),
+    NextLayout,
+    /// The launcher input was edited
+    LauncherInput(String),
+    Spawn(String),
+}
 
 #[derive(Default)]
 pub struct Shell<B: crate::Backend>(PhantomData<B>);
 impl<B: crate::Backend> crate::iced::Program for Shell<B> {
     type Data = crate::state::Compositor<B>;
     type Message = Message;
+    type Layout = Vec<window::WindowBounds>;
 
     fn view(data: &Self::Data) -> impl Into<crate::iced::Element<'_, Self::Message>> {
-        Element::new(
+        let workspace = data.workspace();
+
+        // Fullscreen windows are drawn above the shell, which only provides a black background
+        if workspace.fullscreen.is_some() {
+            let element: crate::iced::Element<'_, Self::Message> = container(horizontal_space())
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .style(|_| container::background(iced_core::Color::BLACK))
+                .into();
+            return element;
+        }
+
+        let tiled: Vec<_> = match &workspace.maximized {
+            Some(window) => vec![window.clone()],
+            None => workspace
+                .windows
+                .iter()
+                .filter(|window| {
+                    !workspace.minimized.contains(window) && workspace.floating(window).is_none()
+                })
+                .cloned()
+                .collect(),
+        };
+        let floating: Vec<_> = workspace
+            .floating
+            .iter()
+            .filter(|floating| {
+                !workspace.minimized.contains(&floating.window)
+                    && workspace.maximized.as_ref() != Some(&floating.window)
+            })
+            .cloned()
+            .collect();
+        let shell_location = data
+            .shell_geometry()
+            .map(|geometry| geometry.loc)
+            .unwrap_or_default();
+
+        let focus = data
+            .seat
+            .get_keyboard()
+            .and_then(|keyboard| keyboard.current_focus());
+        let focused = tiled
+            .iter()
+            .position(|window| crate::util::surface::window_surface(window) == focus);
+        let tiled_count = tiled.len();
+        let tiles = tiled.iter().map(window_tile).collect();
+        let floating_tiles = floating
+            .iter()
+            .map(|floating| window_tile(&floating.window))
+            .collect();
+
+        let tiling = arranged::Arranged::new(
+            move |area| workspace.layout.arrange(area, tiled_count, focused),
+            tiles,
+        );
+        // Floating geometries are global, the shell area is not
+        let floating_layer = arranged::Arranged::new(
+            move |_| {
+                floating
+                    .iter()
+                    .map(|floating| {
+                        let mut geometry = floating.geometry;
+                        geometry.loc -= shell_location;
+                        Some(geometry)
+                    })
+                    .collect()
+            },
+            floating_tiles,
+        );
+
+        Element::new(stack![
             column![
-                row(data.space.elements().map(|window| {
-                    iced_widget::column![
-                        text!("Top"),
-                        window::Window(window.clone()),
-                        text!("Bottom")
-                    ]
-                    .into()
-                })),
-                vertical_space(),
+                tiling,
                 iced_widget::row![
-                    button(text!("Active windows: {}", data.space.elements().len())),
+                    workspace_indicator(data),
+                    button(text!("Active windows: {}", workspace.windows.len()))
+                        .on_press(Message::FocusNext),
+                    button(text(workspace.layout.kind.layout().name()))
+                        .on_press(Message::NextLayout),
+                    row(workspace
+                        .windows
+                        .iter()
+                        .filter_map(|window| taskbar_entry(data, window)))
+                    .spacing(4),
+                    launcher(data),
                     horizontal_space(),
                     text!(
                         "Running for {} seconds",
@@ -38,13 +141,329 @@ impl<B: crate::Backend> crate::iced::Program for Shell<B> {
             ]
             .width(Length::Fill)
             .height(Length::Fill),
-        )
+            floating_layer
+        ])
         .explain(iced_core::color!(0xFF0000))
     }
 
     fn update(
-        _data: &mut Self::Data,
-        _message: Self::Message,
+        data: &mut Self::Data,
+        message: Self::Message,
     ) -> impl Into<iced_runtime::Task<Self::Message>> {
+        let action = match message {
+            Message::FocusWindow(surface) => Action::Focus(surface),
+            Message::FocusNext => Action::FocusNext,
+            Message::CloseWindow(surface) => Action::Close(Target::Window(surface)),
+            Message::RaiseWindow(surface) => Action::Raise(Target::Window(surface)),
+            Message::MoveWindow(surface) => Action::Move(Target::Window(surface)),
+            Message::ToggleFloating(surface) => Action::ToggleFloating(Target::Window(surface)),
+            Message::ToggleMaximize(surface) => Action::ToggleMaximize(Target::Window(surface)),
+            Message::MinimizeWindow(surface) => Action::Minimize(Target::Window(surface)),
+            Message::ToggleFullscreen(surface) => Action::ToggleFullscreen(Target::Window(surface)),
+            Message::SwitchWorkspace(number) => Action::SwitchWorkspace(number),
+            Message::MoveToWorkspace(surface, number) => {
+                Action::MoveToWorkspace(Target::Window(surface), number)
+            }
+            Message::NextLayout => Action::NextLayout,
+            Message::LauncherInput(command) => {
+                data.launcher = command;
+                return;
+            }
+            Message::Spawn(command) => {
+                data.launcher.clear();
+                if command.trim().is_empty() {
+                    return;
+                }
+                Action::Spawn(command)
+            }
+        };
+
+        // Focusing needs the seat, which needs the whole App
+        if let Err(err) = data.actions.send(action) {
+            error!("Unable to queue shell action: {err}");
+        }
+    }
+
+    fn inspect_layout(
+        ui: &mut iced_runtime::UserInterface<
+            '_,
+            Self::Message,
+            crate::iced::Theme,
+            crate::iced::Renderer,
+        >,
+        renderer: &crate::iced::Renderer,
+    ) -> Self::Layout {
+        let mut operation = window::LayoutOperation::default();
+        ui.operate(renderer, &mut operation);
+        operation.0
+    }
+
+    fn write_clipboard(data: &mut Self::Data, kind: iced_core::clipboard::Kind, contents: String) {
+        let target = match kind {
+            iced_core::clipboard::Kind::Standard => SelectionTarget::Clipboard,
+            iced_core::clipboard::Kind::Primary => SelectionTarget::Primary,
+        };
+        data.set_text_selection(target, contents);
+    }
+
+    fn request_redraw(data: &mut Self::Data, request: iced_core::window::RedrawRequest) {
+        match request {
+            iced_core::window::RedrawRequest::NextFrame => data.schedule_redraw(),
+            iced_core::window::RedrawRequest::At(at) => data.schedule_redraw_at(at),
+        }
+    }
+
+    fn apply_layout(data: &mut Self::Data, layout: Self::Layout) {
+        let Some(shell_geometry) = data.shell_geometry() else {
+            return;
+        };
+
+        if let Some(fullscreen) = data.workspace().fullscreen.clone() {
+            Self::apply_fullscreen(data, fullscreen);
+            return;
+        }
+
+        let windows: Vec<_> = layout
+            .into_iter()
+            .filter_map(|window::WindowBounds { surface, bounds }| {
+                crate::util::surface::find_window(&surface, &data.windows)
+                    .map(|window| (window, bounds))
+            })
+            .collect();
+        let workspace = data.workspace();
+        let tiled_count = windows
+            .iter()
+            .filter(|(window, _)| workspace.floating(window).is_none())
+            .count();
+        let placements: Vec<_> = windows
+            .iter()
+            .map(|(window, _)| {
+                if workspace.maximized.as_ref() == Some(window) {
+                    window::Placement::Maximized
+                } else if workspace.floating(window).is_some() {
+                    window::Placement::Floating
+                } else if tiled_count == 1 {
+                    window::Placement::Maximized
+                } else {
+                    window::Placement::Tiled
+                }
+            })
+            .collect();
+
+        // Windows without a widget, like minimized ones, must not get input
+        let hidden: Vec<_> = data
+            .space
+            .elements()
+            .filter(|element| !windows.iter().any(|(window, _)| window == *element))
+            .cloned()
+            .collect();
+        for window in hidden {
+            data.space.unmap_elem(&window);
+        }
+
+        // Keep the client content aligned with the window widgets drawing it
+        for ((window, bounds), placement) in windows.into_iter().zip(placements) {
+            let location =
+                shell_geometry.loc + (bounds.x.round() as i32, bounds.y.round() as i32).into();
+            window::configure(
+                &window,
+                Rectangle::from_loc_and_size(
+                    location,
+                    (bounds.width.round() as i32, bounds.height.round() as i32),
+                ),
+                placement,
+            );
+
+            if data.space.element_location(&window) != Some(location) {
+                data.space.map_element(window, location, false);
+            }
+        }
+
+        // Floating windows have to stay above the tiled ones for input as well
+        let floating: Vec<_> = data
+            .workspace()
+            .floating
+            .iter()
+            .map(|floating| floating.window.clone())
+            .collect();
+        for window in floating {
+            data.space.raise_element(&window, false);
+        }
+    }
+}
+
+impl<B: crate::Backend> Shell<B> {
+    /// Covers the output with the window, leaving no other window visible
+    fn apply_fullscreen(
+        data: &mut crate::state::Compositor<B>,
+        fullscreen: smithay::desktop::Window,
+    ) {
+        let Some(output_geometry) = data
+            .shell_output()
+            .and_then(|output| data.space.output_geometry(output))
+        else {
+            return;
+        };
+
+        let hidden: Vec<_> = data
+            .space
+            .elements()
+            .filter(|window| *window != &fullscreen)
+            .cloned()
+            .collect();
+        for window in hidden {
+            data.space.unmap_elem(&window);
+        }
+
+        window::configure(&fullscreen, output_geometry, window::Placement::Fullscreen);
+
+        if data.space.element_location(&fullscreen) != Some(output_geometry.loc) {
+            data.space
+                .map_element(fullscreen, output_geometry.loc, false);
+        }
     }
 }
+
+/// Number of the workspace after the existing ones
+fn next_workspace<B: crate::Backend>(data: &crate::state::Compositor<B>) -> usize {
+    data.workspaces
+        .iter()
+        .map(|(number, _)| number)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+/// Buttons for switching to the existing workspaces and creating a new one
+fn workspace_indicator<B: crate::Backend>(
+    data: &crate::state::Compositor<B>,
+) -> crate::iced::Element<'static, Message> {
+    let shown = data.shown_workspace();
+    let next = next_workspace(data);
+
+    iced_widget::row(data.workspaces.iter().map(|(number, _)| {
+        button(text(number))
+            .style(if number == shown {
+                button::primary
+            } else {
+                button::secondary
+            })
+            .on_press(Message::SwitchWorkspace(number))
+            .into()
+    }))
+    .push(button(text("+")).on_press(Message::SwitchWorkspace(next)))
+    .spacing(2)
+    .into()
+}
+
+/// Space taken by the title bar the shell draws above the client content, if any
+pub fn decoration(window: &smithay::desktop::Window) -> Size<i32, Logical> {
+    if window::server_side_decorated(window) {
+        (0, TITLE_BAR_HEIGHT).into()
+    } else {
+        (0, 0).into()
+    }
+}
+
+/// The window with a title bar, unless the client draws its own decorations.
+/// Clicking the parts of the title bar that are not buttons focuses the window
+fn window_tile(window: &smithay::desktop::Window) -> crate::iced::Element<'static, Message> {
+    let content = window::Window(window.clone());
+
+    let tile: crate::iced::Element<'static, Message> = match title_bar(window) {
+        Some(title_bar) => iced_widget::column![title_bar, content].into(),
+        None => content.into(),
+    };
+    match crate::util::surface::window_surface(window) {
+        Some(surface) => mouse_area(tile)
+            .on_press(Message::FocusWindow(surface))
+            .into(),
+        None => tile,
+    }
+}
+
+/// Title and app id with a drag area for moving the window and the window buttons
+fn title_bar(window: &smithay::desktop::Window) -> Option<crate::iced::Element<'static, Message>> {
+    if !window::server_side_decorated(window) {
+        return None;
+    }
+
+    let surface = crate::util::surface::window_surface(window)?;
+    let (title, app_id) = crate::util::surface::title_and_app_id(window);
+
+    Some(
+        iced_widget::row![
+            mouse_area(
+                iced_widget::row![
+                    text(title.unwrap_or_default()),
+                    text(app_id.unwrap_or_default()).size(12)
+                ]
+                .spacing(8)
+                .align_y(Vertical::Center)
+                .width(Length::Fill)
+            )
+            .on_press(Message::MoveWindow(surface.clone())),
+            button(text("~")).on_press(Message::ToggleFloating(surface.clone())),
+            button(text("_")).on_press(Message::MinimizeWindow(surface.clone())),
+            button(text("[]")).on_press(Message::ToggleMaximize(surface.clone())),
+            button(text("X")).on_press(Message::CloseWindow(surface)),
+        ]
+        .spacing(4)
+        .padding(2)
+        .height(TITLE_BAR_HEIGHT as f32)
+        .align_y(Vertical::Center)
+        .into(),
+    )
+}
+
+/// Taskbar button focusing the window or bringing it back when minimized,
+/// with buttons for fullscreen and closing and a menu for moving it to another workspace
+fn taskbar_entry<B: crate::Backend>(
+    data: &crate::state::Compositor<B>,
+    window: &smithay::desktop::Window,
+) -> Option<crate::iced::Element<'static, Message>> {
+    let surface = crate::util::surface::window_surface(window)?;
+    let (title, app_id) = crate::util::surface::title_and_app_id(window);
+
+    let workspaces: Vec<_> = data
+        .workspaces
+        .iter()
+        .map(|(number, _)| number)
+        .chain([next_workspace(data)])
+        .collect();
+    let move_to_workspace = {
+        let surface = surface.clone();
+        pick_list(
+            workspaces,
+            data.workspaces.number_of(window),
+            move |number| Message::MoveToWorkspace(surface.clone(), number),
+        )
+    };
+
+    Some(
+        iced_widget::row![
+            button(text(title.or(app_id).unwrap_or_default())).on_press(
+                if data.workspace().minimized.contains(window) {
+                    Message::RaiseWindow(surface.clone())
+                } else {
+                    Message::FocusWindow(surface.clone())
+                }
+            ),
+            move_to_workspace,
+            button(text("F")).on_press(Message::ToggleFullscreen(surface.clone())),
+            button(text("X")).on_press(Message::CloseWindow(surface)),
+        ]
+        .into(),
+    )
+}
+
+/// Input running the typed shell command when enter is pressed
+fn launcher<B: crate::Backend>(
+    data: &crate::state::Compositor<B>,
+) -> crate::iced::Element<'static, Message> {
+    text_input("Run...", &data.launcher)
+        .on_input(Message::LauncherInput)
+        .on_submit(Message::Spawn(data.launcher.clone()))
+        .width(200)
+        .into()
+}
@@ -1,11 +1,164 @@
+use smithay::{
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, Rectangle},
+    wayland::shell::xdg::XdgToplevelSurfaceData,
+    xwayland::{xwm::X11SurfaceError, X11Surface},
+};
+use std::any::Any;
+use tracing::warn;
+
 pub struct Window(pub smithay::desktop::Window);
 
 impl Window {
-    fn width(&self) -> f32 {
-        self.0.bbox().size.w as f32
+    /// Shrinks or grows the allocated size to what the client accepts
+    fn clamp_to_hints(&self, size: iced_core::Size) -> iced_core::Size {
+        let (min, max) = crate::util::surface::size_hints(&self.0);
+        let clamp = |value: f32, min: i32, max: i32| {
+            let value = value.max(min as f32);
+            if max > 0 {
+                value.min(max as f32)
+            } else {
+                value
+            }
+        };
+
+        iced_core::Size::new(
+            clamp(size.width, min.w, max.w),
+            clamp(size.height, min.h, max.h),
+        )
+    }
+}
+
+/// How a window is placed by the shell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    Tiled,
+    /// Placed by the user instead of the layout
+    Floating,
+    Maximized,
+    Fullscreen,
+}
+
+/// Whether the client agreed on the shell drawing its title bar
+pub fn server_side_decorated(window: &smithay::desktop::Window) -> bool {
+    use smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode;
+
+    // X11 clients that do not draw their own decorations expect the window manager to
+    if let Some(x11) = window.x11_surface() {
+        return !x11.is_decorated();
+    }
+
+    window
+        .toplevel()
+        .is_some_and(|toplevel| toplevel.current_state().decoration_mode == Some(Mode::ServerSide))
+}
+
+/// Configures the window with the geometry allocated by the shell, in global coordinates.
+/// The configure of xdg toplevels is only sent once the client did its initial commit
+pub fn configure(
+    window: &smithay::desktop::Window,
+    geometry: Rectangle<i32, Logical>,
+    placement: Placement,
+) {
+    use smithay::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel::State;
+
+    if let Some(x11) = window.x11_surface() {
+        if let Err(err) = configure_x11(x11, geometry, placement) {
+            warn!("Unable to configure X11 window: {err}");
+        }
+        return;
     }
-    fn height(&self) -> f32 {
-        self.0.bbox().size.h as f32
+
+    let Some(toplevel) = window.toplevel() else {
+        return;
+    };
+
+    toplevel.with_pending_state(|state| {
+        state.size = Some(geometry.size);
+
+        for tiled in [
+            State::TiledLeft,
+            State::TiledRight,
+            State::TiledTop,
+            State::TiledBottom,
+        ] {
+            if placement == Placement::Floating {
+                state.states.unset(tiled);
+            } else {
+                state.states.set(tiled);
+            }
+        }
+
+        for (flag, enabled) in [
+            (State::Maximized, placement == Placement::Maximized),
+            (State::Fullscreen, placement == Placement::Fullscreen),
+        ] {
+            if enabled {
+                state.states.set(flag);
+            } else {
+                state.states.unset(flag);
+            }
+        }
+    });
+
+    let initial_configure_sent = crate::util::surface::with_surface_data(
+        toplevel.wl_surface(),
+        |mutex: &XdgToplevelSurfaceData| mutex.lock().unwrap().initial_configure_sent,
+    );
+
+    if let Some(true) = initial_configure_sent {
+        toplevel.send_pending_configure();
+    }
+}
+
+/// X11 windows are positioned by the window manager, so they also get the location.
+/// Nothing is sent while the window already matches
+fn configure_x11(
+    x11: &X11Surface,
+    geometry: Rectangle<i32, Logical>,
+    placement: Placement,
+) -> Result<(), X11SurfaceError> {
+    let maximized = placement == Placement::Maximized;
+    if x11.is_maximized() != maximized {
+        x11.set_maximized(maximized)?;
+    }
+
+    let fullscreen = placement == Placement::Fullscreen;
+    if x11.is_fullscreen() != fullscreen {
+        x11.set_fullscreen(fullscreen)?;
+    }
+
+    if x11.geometry() != geometry {
+        x11.configure(geometry)?;
+    }
+    Ok(())
+}
+
+/// Where a [Window] widget ended up after layout
+#[derive(Debug, Clone)]
+pub struct WindowBounds {
+    pub surface: WlSurface,
+    pub bounds: iced_core::Rectangle,
+}
+
+/// Collects the [WindowBounds] of all [Window] widgets
+#[derive(Default)]
+pub struct LayoutOperation(pub Vec<WindowBounds>);
+
+impl iced_core::widget::Operation for LayoutOperation {
+    fn container(
+        &mut self,
+        _id: Option<&iced_core::widget::Id>,
+        _bounds: iced_core::Rectangle,
+        operate_on_children: &mut dyn FnMut(&mut dyn iced_core::widget::Operation),
+    ) {
+        operate_on_children(self);
+    }
+
+    fn custom(&mut self, state: &mut dyn Any, _id: Option<&iced_core::widget::Id>) {
+        if let Some(window_bounds) = state.downcast_ref::<WindowBounds>() {
+            self.0.push(window_bounds.clone());
+        }
     }
 }
 
@@ -13,16 +166,40 @@ impl<Message, Theme, Renderer: iced_wgpu::primitive::Renderer>
     iced_core::Widget<Message, Theme, Renderer> for Window
 {
     fn size(&self) -> iced_core::Size<iced_core::Length> {
-        iced_core::Size::new(self.width().into(), self.height().into())
+        iced_core::Size::new(iced_core::Length::Fill, iced_core::Length::Fill)
     }
 
     fn layout(
         &self,
         _tree: &mut iced_core::widget::Tree,
         _renderer: &Renderer,
-        _limits: &iced_core::layout::Limits,
+        limits: &iced_core::layout::Limits,
     ) -> iced_core::layout::Node {
-        iced_core::layout::Node::new((self.width(), self.height()).into())
+        // The shell decides the size, the client is configured accordingly afterwards
+        let allocated = limits.resolve(
+            iced_core::Length::Fill,
+            iced_core::Length::Fill,
+            iced_core::Size::ZERO,
+        );
+        iced_core::layout::Node::new(self.clamp_to_hints(allocated))
+    }
+
+    fn operate(
+        &self,
+        _tree: &mut iced_core::widget::Tree,
+        layout: iced_core::Layout<'_>,
+        _renderer: &Renderer,
+        operation: &mut dyn iced_core::widget::Operation,
+    ) {
+        if let Some(surface) = crate::util::surface::window_surface(&self.0) {
+            operation.custom(
+                &mut WindowBounds {
+                    surface,
+                    bounds: layout.bounds(),
+                },
+                None,
+            );
+        }
     }
 
     fn draw(
@@ -35,7 +212,16 @@ impl<Message, Theme, Renderer: iced_wgpu::primitive::Renderer>
         _cursor: iced_core::mouse::Cursor,
         _viewport: &iced_core::Rectangle,
     ) {
-        renderer.draw_primitive(layout.bounds(), crate::iced::scissors::ScissorPrimitive {});
+        let Some(surface) = crate::util::surface::window_surface(&self.0) else {
+            return;
+        };
+
+        // The bounds match the window geometry, which may leave out parts of the surface
+        let location = Point::default() - self.0.geometry().loc;
+        renderer.draw_primitive(
+            layout.bounds(),
+            crate::iced::surface::SurfacePrimitive::new(&surface, location),
+        );
     }
 }
 
@@ -17,6 +17,9 @@ pub fn init_socket<B: crate::Backend>(
     let listening_socket =
         ListeningSocketSource::new_auto().expect("Unable to create wayland socket");
 
+    // Processes spawned by the compositor should connect to it
+    std::env::set_var("WAYLAND_DISPLAY", listening_socket.socket_name());
+
     app.common
         .comp
         .loop_handle
@@ -1,7 +1,7 @@
 use smithay::{
-    desktop, input,
+    desktop, input, output,
     reexports::{calloop, wayland_server},
-    wayland,
+    utils, wayland,
 };
 
 pub struct App<B: crate::Backend> {
@@ -12,6 +12,7 @@ pub struct App<B: crate::Backend> {
 pub struct Common<B: crate::Backend> {
     pub comp: Compositor<B>,
     pub shell_driver: crate::iced::Driver<crate::shell::Shell<B>>,
+    pub shell_buffer: Option<crate::render::ShellBuffer>,
 }
 
 pub struct Compositor<B: crate::Backend> {
@@ -22,8 +23,35 @@ pub struct Compositor<B: crate::Backend> {
     pub wl: WaylandState<B>,
 
     pub start_time: std::time::Instant,
+    /// Clock of the presentation timestamps
+    pub clock: utils::Clock<utils::Monotonic>,
     pub seat: input::Seat<App<B>>,
     pub space: desktop::Space<desktop::Window>,
+    /// Toplevel windows of all workspaces
+    pub windows: Vec<desktop::Window>,
+    pub workspaces: crate::workspace::Workspaces,
+    pub popups: desktop::PopupManager,
+    /// Menus and tooltips of X11 clients, placed by the clients themselves. Topmost last
+    pub override_redirect: Vec<smithay::xwayland::X11Surface>,
+    /// Window manager of the Xwayland server, once it is ready
+    pub xwm: Option<smithay::xwayland::X11Wm>,
+    /// X11 display number of the Xwayland server
+    pub xdisplay: Option<u32>,
+    pub config: crate::config::Config,
+    pub ipc: crate::ipc::Ipc,
+    pub redraw: crate::redraw::Scheduler,
+    pub keybindings: crate::keybindings::Keybindings,
+    /// Queues actions from places without access to the whole [crate::App]
+    pub actions: calloop::channel::Sender<crate::action::Action>,
+    /// Icon of the ongoing drag-and-drop, drawn at the pointer
+    pub dnd_icon: Option<DndIcon>,
+    pub pointer_location: utils::Point<f64, utils::Logical>,
+    /// Buttons held since they were pressed on the shell.
+    /// The pointer of the seat only knows about the buttons pressed on clients
+    pub shell_buttons: Vec<u32>,
+    pub selection_readers: crate::handlers::selection::SelectionReaders,
+    /// Command typed into the launcher of the shell bar
+    pub launcher: String,
 }
 
 pub struct WaylandState<B: crate::Backend> {
@@ -31,7 +59,15 @@ pub struct WaylandState<B: crate::Backend> {
     pub seat: input::SeatState<App<B>>,
     pub shm: wayland::shm::ShmState,
     pub xdg_shell: wayland::shell::xdg::XdgShellState,
+    pub xdg_decoration: wayland::shell::xdg::decoration::XdgDecorationState,
+    pub layer_shell: wayland::shell::wlr_layer::WlrLayerShellState,
     pub dmabuf: wayland::dmabuf::DmabufState,
+    pub data_device: wayland::selection::data_device::DataDeviceState,
+    pub primary_selection: wayland::selection::primary_selection::PrimarySelectionState,
+    pub xwayland_shell: wayland::xwayland_shell::XWaylandShellState,
+    pub fractional_scale: wayland::fractional_scale::FractionalScaleManagerState,
+    pub viewporter: wayland::viewporter::ViewporterState,
+    pub presentation: wayland::presentation::PresentationState,
 }
 
 impl<B: crate::Backend> Compositor<B> {
@@ -39,16 +75,42 @@ impl<B: crate::Backend> Compositor<B> {
         display_handle: wayland_server::DisplayHandle,
         loop_handle: calloop::LoopHandle<'static, App<B>>,
         loop_signal: calloop::LoopSignal,
+        config: crate::config::Config,
     ) -> Self {
+        let clock = utils::Clock::new();
         let mut wl = WaylandState {
             compositor: wayland::compositor::CompositorState::new::<App<B>>(&display_handle),
             seat: input::SeatState::new(),
             shm: wayland::shm::ShmState::new::<App<B>>(&display_handle, []),
             xdg_shell: wayland::shell::xdg::XdgShellState::new::<App<B>>(&display_handle),
+            xdg_decoration: wayland::shell::xdg::decoration::XdgDecorationState::new::<App<B>>(
+                &display_handle,
+            ),
+            layer_shell: wayland::shell::wlr_layer::WlrLayerShellState::new::<App<B>>(
+                &display_handle,
+            ),
             dmabuf: wayland::dmabuf::DmabufState::new(),
+            data_device: wayland::selection::data_device::DataDeviceState::new::<App<B>>(
+                &display_handle,
+            ),
+            primary_selection: wayland::selection::primary_selection::PrimarySelectionState::new::<
+                App<B>,
+            >(&display_handle),
+            xwayland_shell: wayland::xwayland_shell::XWaylandShellState::new::<App<B>>(
+                &display_handle,
+            ),
+            fractional_scale: wayland::fractional_scale::FractionalScaleManagerState::new::<App<B>>(
+                &display_handle,
+            ),
+            viewporter: wayland::viewporter::ViewporterState::new::<App<B>>(&display_handle),
+            presentation: wayland::presentation::PresentationState::new::<App<B>>(
+                &display_handle,
+                clock.id() as u32,
+            ),
         };
 
         let seat = wl.seat.new_wl_seat(&display_handle, "default");
+        let actions = crate::action::init_bus(&loop_handle);
 
         Self {
             display_handle,
@@ -56,10 +118,154 @@ impl<B: crate::Backend> Compositor<B> {
             loop_signal,
             wl,
             start_time: std::time::Instant::now(),
+            clock,
             seat,
             space: desktop::Space::default(),
+            windows: Vec::new(),
+            workspaces: crate::workspace::Workspaces::default(),
+            popups: desktop::PopupManager::default(),
+            override_redirect: Vec::new(),
+            xwm: None,
+            xdisplay: None,
+            config,
+            ipc: crate::ipc::Ipc::default(),
+            redraw: crate::redraw::Scheduler::default(),
+            keybindings: crate::keybindings::Keybindings::default(),
+            actions,
+            dnd_icon: None,
+            pointer_location: (0., 0.).into(),
+            shell_buttons: Vec::new(),
+            selection_readers: Default::default(),
+            launcher: String::new(),
         }
     }
+
+    /// The output the shell is displayed on
+    pub fn shell_output(&self) -> Option<&output::Output> {
+        self.space.outputs().next()
+    }
+
+    /// The area covered by the shell in global coordinates.
+    /// Exclusive zones of layer surfaces are left out
+    pub fn shell_geometry(&self) -> Option<utils::Rectangle<i32, utils::Logical>> {
+        let output = self.shell_output()?;
+        let output_geometry = self.space.output_geometry(output)?;
+
+        let mut zone = desktop::layer_map_for_output(output).non_exclusive_zone();
+        zone.loc += output_geometry.loc;
+        Some(zone)
+    }
+
+    /// Number of the workspace shown on the shell output.
+    /// Only the shell output draws its workspace, the others are assigned one nonetheless
+    pub fn shown_workspace(&self) -> usize {
+        self.workspaces.shown_on(self.shell_output())
+    }
+
+    /// The workspace shown on the shell output
+    pub fn workspace(&self) -> &crate::workspace::Workspace {
+        self.workspaces
+            .get(self.shown_workspace())
+            .expect("The shown workspace always exists")
+    }
+
+    pub fn workspace_mut(&mut self) -> &mut crate::workspace::Workspace {
+        let number = self.shown_workspace();
+        self.workspaces.get_or_create(number)
+    }
+
+    /// Whether the window is on the workspace shown on the shell output
+    pub fn is_on_shown_workspace(&self, window: &desktop::Window) -> bool {
+        self.workspaces.number_of(window) == Some(self.shown_workspace())
+    }
+
+    /// The floating state of the window, if it is not tiled
+    pub fn floating(&self, window: &desktop::Window) -> Option<&crate::layout::Floating> {
+        self.workspaces.containing(window)?.floating(window)
+    }
+
+    pub fn floating_mut(
+        &mut self,
+        window: &desktop::Window,
+    ) -> Option<&mut crate::layout::Floating> {
+        self.workspaces.containing_mut(window)?.floating_mut(window)
+    }
+
+    /// Takes the window out of the layout, keeping it where it currently is.
+    /// Windows that are not mapped are centered on the shell instead
+    pub fn float(&mut self, window: &desktop::Window) {
+        if self.floating(window).is_some() {
+            return;
+        }
+
+        // Floating geometries include the title bar above the client content
+        let outer_geometry = self.space.element_geometry(window).map(|content| {
+            let decoration = crate::shell::decoration(window);
+            utils::Rectangle::from_loc_and_size(
+                content.loc - utils::Point::from((0, decoration.h)),
+                content.size + decoration,
+            )
+        });
+        let Some(geometry) = outer_geometry.or_else(|| {
+            let shell = self.shell_geometry()?;
+            Some(utils::Rectangle::from_loc_and_size(
+                (
+                    shell.loc.x + shell.size.w / 4,
+                    shell.loc.y + shell.size.h / 4,
+                ),
+                (shell.size.w / 2, shell.size.h / 2),
+            ))
+        }) else {
+            return;
+        };
+
+        if let Some(workspace) = self.workspaces.containing_mut(window) {
+            workspace.floating.push(crate::layout::Floating {
+                window: window.clone(),
+                geometry,
+            });
+        }
+    }
+
+    pub fn shell_bounds(&self) -> crate::iced::Bounds {
+        self.shell_geometry()
+            .map(|geometry| (geometry.size.w as u32, geometry.size.h as u32).into())
+            .unwrap_or_default()
+    }
+}
+
+impl<B: crate::Backend> Common<B> {
+    pub fn new(
+        display_handle: wayland_server::DisplayHandle,
+        loop_handle: calloop::LoopHandle<'static, App<B>>,
+        loop_signal: calloop::LoopSignal,
+        config: crate::config::Config,
+    ) -> Self {
+        let comp = Compositor::new(display_handle, loop_handle.clone(), loop_signal, config);
+        let shell_driver = crate::iced::Driver::new(
+            std::sync::Arc::new(futures::executor::block_on(
+                crate::iced::wgpu::Objects::new(),
+            )),
+            loop_handle,
+            |app| {
+                let bounds = app.common.comp.shell_bounds();
+                (&mut app.common.shell_driver, &mut app.common.comp, bounds)
+            },
+        );
+
+        Self {
+            comp,
+            shell_driver,
+            shell_buffer: None,
+        }
+    }
+}
+
+/// Surface of a drag-and-drop icon
+pub struct DndIcon {
+    pub surface: wayland_server::protocol::wl_surface::WlSurface,
+    /// Hotspot of the icon, moved by the offsets the client attaches buffers with
+    pub offset: utils::Point<i32, utils::Logical>,
 }
 
 #[derive(Default)]
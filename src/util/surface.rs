@@ -1,16 +1,44 @@
-use smithay::{reexports::wayland_server::protocol::wl_surface::WlSurface, wayland};
+use smithay::{
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Size},
+    wayland::{
+        self,
+        seat::WaylandFocus,
+        shell::xdg::{SurfaceCachedState, XdgToplevelSurfaceData},
+    },
+};
+use tracing::error;
 
 /// Tries finding the [smithay::desktop::Window] that the given root [WlSurface] belongs to
 pub fn find_window(
     root_surface: &WlSurface,
-    space: &smithay::desktop::Space<smithay::desktop::Window>,
+    windows: &[smithay::desktop::Window],
 ) -> Option<smithay::desktop::Window> {
-    space
-        .elements()
-        .find(|window| window.toplevel().map(|s| s.wl_surface()) == Some(root_surface))
+    windows
+        .iter()
+        .find(|window| window_surface(window).as_ref() == Some(root_surface))
         .cloned()
 }
 
+/// Returns the root [WlSurface] of the window.
+/// X11 windows only have one once Xwayland associated it with the X11 window
+pub fn window_surface(window: &smithay::desktop::Window) -> Option<WlSurface> {
+    window
+        .wl_surface()
+        .map(|surface| WlSurface::clone(&surface))
+}
+
+/// Asks the client to close the window
+pub fn close_window(window: &smithay::desktop::Window) {
+    if let Some(toplevel) = window.toplevel() {
+        toplevel.send_close();
+    } else if let Some(x11) = window.x11_surface() {
+        if let Err(err) = x11.close() {
+            error!("Unable to close X11 window: {err}");
+        }
+    }
+}
+
 /// Returns the root parent [WlSurface]
 pub fn get_root_surface(surface: &WlSurface) -> WlSurface {
     if let Some(parent) = wayland::compositor::get_parent(surface) {
@@ -25,3 +53,42 @@ pub fn get_root_surface(surface: &WlSurface) -> WlSurface {
 pub fn with_surface_data<D: 'static, T>(surface: &WlSurface, f: impl FnOnce(&D) -> T) -> Option<T> {
     wayland::compositor::with_states(surface, |states| states.data_map.get::<D>().map(f))
 }
+
+/// Returns the minimum and maximum size requested by the client. Zero means unbounded
+pub fn size_hints(window: &smithay::desktop::Window) -> (Size<i32, Logical>, Size<i32, Logical>) {
+    if let Some(x11) = window.x11_surface() {
+        return (
+            x11.min_size().unwrap_or_default(),
+            x11.max_size().unwrap_or_default(),
+        );
+    }
+
+    let Some(toplevel) = window.toplevel() else {
+        return Default::default();
+    };
+
+    wayland::compositor::with_states(toplevel.wl_surface(), |states| {
+        let mut cached = states.cached_state.get::<SurfaceCachedState>();
+        let current = cached.current();
+        (current.min_size, current.max_size)
+    })
+}
+
+/// Returns the title and app id of the toplevel.
+/// X11 windows use their class as app id
+pub fn title_and_app_id(window: &smithay::desktop::Window) -> (Option<String>, Option<String>) {
+    if let Some(x11) = window.x11_surface() {
+        let non_empty = |value: String| (!value.is_empty()).then_some(value);
+        return (non_empty(x11.title()), non_empty(x11.class()));
+    }
+
+    window
+        .toplevel()
+        .and_then(|toplevel| {
+            with_surface_data(toplevel.wl_surface(), |mutex: &XdgToplevelSurfaceData| {
+                let data = mutex.lock().unwrap();
+                (data.title.clone(), data.app_id.clone())
+            })
+        })
+        .unwrap_or_default()
+}
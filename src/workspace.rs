@@ -0,0 +1,203 @@
+use crate::layout::{Floating, LayoutState};
+use smithay::{desktop, output};
+use std::collections::BTreeMap;
+
+/// Windows that are shown together, with their own layout
+#[derive(Default)]
+pub struct Workspace {
+    /// Toplevel windows in the order the shell lays them out
+    pub windows: Vec<desktop::Window>,
+    pub layout: LayoutState,
+    /// Windows placed by the user, stacked above the tiled ones with the topmost one last
+    pub floating: Vec<Floating>,
+    /// Window covering the whole output, above the shell and its bars
+    pub fullscreen: Option<desktop::Window>,
+    /// Window taking all the space left by the bars
+    pub maximized: Option<desktop::Window>,
+    /// Windows only shown in the taskbar
+    pub minimized: Vec<desktop::Window>,
+}
+
+impl Workspace {
+    /// The floating state of the window, if it is not tiled
+    pub fn floating(&self, window: &desktop::Window) -> Option<&Floating> {
+        self.floating
+            .iter()
+            .find(|floating| &floating.window == window)
+    }
+
+    pub fn floating_mut(&mut self, window: &desktop::Window) -> Option<&mut Floating> {
+        self.floating
+            .iter_mut()
+            .find(|floating| &floating.window == window)
+    }
+
+    /// Puts the window back into the layout
+    pub fn tile(&mut self, window: &desktop::Window) {
+        self.floating.retain(|floating| &floating.window != window);
+    }
+
+    /// Fullscreen windows are shown even when they were minimized
+    pub fn set_fullscreen(&mut self, window: &desktop::Window, fullscreen: bool) {
+        if fullscreen {
+            self.minimized.retain(|minimized| minimized != window);
+            self.fullscreen = Some(window.clone());
+        } else if self.fullscreen.as_ref() == Some(window) {
+            self.fullscreen = None;
+        }
+    }
+
+    /// Maximized windows are shown even when they were minimized
+    pub fn set_maximized(&mut self, window: &desktop::Window, maximized: bool) {
+        if maximized {
+            self.minimized.retain(|minimized| minimized != window);
+            self.maximized = Some(window.clone());
+        } else if self.maximized.as_ref() == Some(window) {
+            self.maximized = None;
+        }
+    }
+
+    /// Takes the window and everything known about it off the workspace
+    pub fn remove(&mut self, window: &desktop::Window) {
+        self.windows.retain(|other| other != window);
+        self.tile(window);
+        self.set_fullscreen(window, false);
+        self.set_maximized(window, false);
+        self.minimized.retain(|minimized| minimized != window);
+    }
+}
+
+/// Workspaces numbered from one. They are created when they are switched to
+/// and removed again once they are empty and not shown on any output
+pub struct Workspaces {
+    workspaces: BTreeMap<usize, Workspace>,
+    /// Number of the workspace shown on each output
+    shown: Vec<(output::Output, usize)>,
+}
+
+impl Default for Workspaces {
+    fn default() -> Self {
+        Self {
+            workspaces: BTreeMap::from([(1, Workspace::default())]),
+            shown: Vec::new(),
+        }
+    }
+}
+
+impl Workspaces {
+    /// Number of the workspace shown on the output.
+    /// Outputs without an assigned workspace show the first one
+    pub fn shown_on(&self, output: Option<&output::Output>) -> usize {
+        output
+            .and_then(|output| self.shown.iter().find(|(shown, _)| shown == output))
+            .map(|(_, number)| *number)
+            .or_else(|| self.workspaces.keys().next().copied())
+            .unwrap_or(1)
+    }
+
+    /// The output the workspace is shown on
+    pub fn output_of(&self, number: usize) -> Option<&output::Output> {
+        self.shown
+            .iter()
+            .find(|(_, shown)| *shown == number)
+            .map(|(output, _)| output)
+    }
+
+    pub fn get(&self, number: usize) -> Option<&Workspace> {
+        self.workspaces.get(&number)
+    }
+
+    /// Returns the workspace, creating it if it does not exist yet
+    pub fn get_or_create(&mut self, number: usize) -> &mut Workspace {
+        self.workspaces.entry(number).or_default()
+    }
+
+    /// The existing workspaces ordered by their number
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Workspace)> {
+        self.workspaces
+            .iter()
+            .map(|(number, workspace)| (*number, workspace))
+    }
+
+    /// Number of the workspace the window is on
+    pub fn number_of(&self, window: &desktop::Window) -> Option<usize> {
+        self.iter()
+            .find(|(_, workspace)| workspace.windows.contains(window))
+            .map(|(number, _)| number)
+    }
+
+    pub fn containing(&self, window: &desktop::Window) -> Option<&Workspace> {
+        self.get(self.number_of(window)?)
+    }
+
+    pub fn containing_mut(&mut self, window: &desktop::Window) -> Option<&mut Workspace> {
+        let number = self.number_of(window)?;
+        self.workspaces.get_mut(&number)
+    }
+
+    /// Shows the workspace on the output, creating it if needed.
+    /// If it is already shown on another output, the outputs swap their workspaces
+    pub fn show(&mut self, output: &output::Output, number: usize) {
+        self.get_or_create(number);
+
+        let previous = self.shown_on(Some(output));
+        for (_, shown) in &mut self.shown {
+            if *shown == number {
+                *shown = previous;
+            }
+        }
+
+        match self.shown.iter_mut().find(|(shown, _)| shown == output) {
+            Some((_, shown)) => *shown = number,
+            None => self.shown.push((output.clone(), number)),
+        }
+
+        self.remove_unused();
+    }
+
+    /// Forgets dead windows and removed outputs, and gives new outputs a workspace
+    pub fn refresh(&mut self, outputs: &[output::Output]) {
+        for workspace in self.workspaces.values_mut() {
+            let dead: Vec<_> = workspace
+                .windows
+                .iter()
+                .filter(|window| !window.alive())
+                .cloned()
+                .collect();
+            for window in dead {
+                workspace.remove(&window);
+            }
+        }
+
+        self.shown.retain(|(output, _)| outputs.contains(output));
+        for output in outputs {
+            if self.shown.iter().any(|(shown, _)| shown == output) {
+                continue;
+            }
+
+            // Prefer an existing workspace that is not shown anywhere yet
+            let number = self
+                .workspaces
+                .keys()
+                .copied()
+                .find(|number| self.output_of(*number).is_none())
+                .or_else(|| (1..).find(|number| self.output_of(*number).is_none()))
+                .unwrap_or(1);
+            self.get_or_create(number);
+            self.shown.push((output.clone(), number));
+        }
+
+        self.remove_unused();
+    }
+
+    /// Removes empty workspaces that are not shown, keeping at least one
+    fn remove_unused(&mut self) {
+        let shown: Vec<_> = self.shown.iter().map(|(_, number)| *number).collect();
+        self.workspaces
+            .retain(|number, workspace| !workspace.windows.is_empty() || shown.contains(number));
+
+        if self.workspaces.is_empty() {
+            self.workspaces.insert(1, Workspace::default());
+        }
+    }
+}